@@ -1,10 +1,152 @@
-use macroquad::prelude::Vec2;
-use crate::{markers::Marker, food::Food};
+use std::{io, f32::consts::PI};
+
+use macroquad::prelude::{Color, WHITE};
+use crate::{food::{Food, FoodID}, gobj::{ANT_RAD, ANT_SPEED, HARD_BOUNDS}, prelude::{ByteWriter, ByteReader, invalid_data, Rng}};
 
 #[derive(PartialEq, Debug, Clone)]
 pub enum AntState {
 	Wander(f32, f32, f32),
-	GetFood(Vec2),
-	Follow(Option<Marker>),
-	GoHome(Food, Option<Marker>, f32, f32, f32),
+	GetFood(FoodID),
+	Follow,
+	/// The last field is how far this ant has carried the food so far.
+	GoHome(Food, f32, f32, f32, f32),
+	/// Low on energy, heading home to eat.
+	Hungry,
+	/// Smelled alarm, reacting for the given number of seconds.
+	Alarmed(f32),
+}
+/// Left, centre and right probes around the heading, each smelling the
+/// trail within `radius` of its tip.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Antennae {
+	pub angle : f32,
+	pub distance : f32,
+	pub radius : f32,
+}
+pub const ANTENNAE : Antennae = Antennae {
+	angle: PI/4.,
+	distance: ANT_RAD*5.,
+	radius: ANT_RAD*3.,
+};
+
+/// Which markers a caste drops as it goes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Marking {
+	/// Home markers on the way out, food markers on the way back.
+	Trail,
+	/// Home markers wherever it goes.
+	HomeOnly,
+	Off,
+}
+/// What a caste does when it smells alarm.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AlarmResponse {
+	Flee,
+	Gather,
+}
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CasteParams {
+	pub speed : f32,
+	pub radius : f32,
+	pub antennae : Antennae,
+	/// Heaviest food it'll pick up, 0 for castes that don't forage.
+	pub carry : f32,
+	pub marking : Marking,
+	pub tint : Color,
+	/// How far from home it'll wander before turning back.
+	pub patrol_radius : f32,
+	pub alarm : AlarmResponse,
+}
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Caste {
+	Worker,
+	Scout,
+	Soldier,
+}
+pub const CASTES : [Caste; 3] = [Caste::Worker, Caste::Scout, Caste::Soldier];
+const WORKER : CasteParams = CasteParams {
+	speed: ANT_SPEED,
+	radius: ANT_RAD,
+	antennae: ANTENNAE,
+	carry: 5.,
+	marking: Marking::Trail,
+	tint: WHITE,
+	patrol_radius: f32::INFINITY,
+	alarm: AlarmResponse::Flee,
+};
+const SCOUT : CasteParams = CasteParams {
+	speed: ANT_SPEED*1.4,
+	radius: ANT_RAD*0.8,
+	antennae: Antennae { distance: ANT_RAD*7., ..ANTENNAE },
+	carry: 0.,
+	marking: Marking::HomeOnly,
+	tint: Color::new(0.7, 0.85, 1., 1.),
+	patrol_radius: f32::INFINITY,
+	alarm: AlarmResponse::Flee,
+};
+const SOLDIER : CasteParams = CasteParams {
+	speed: ANT_SPEED*0.8,
+	radius: ANT_RAD*1.3,
+	antennae: ANTENNAE,
+	carry: 0.,
+	marking: Marking::Off,
+	tint: Color::new(1., 0.6, 0.55, 1.),
+	patrol_radius: HARD_BOUNDS.x*0.3,
+	alarm: AlarmResponse::Gather,
+};
+impl Caste {
+	pub fn params(self) -> &'static CasteParams {
+		match self {
+			Caste::Worker => &WORKER,
+			Caste::Scout => &SCOUT,
+			Caste::Soldier => &SOLDIER,
+		}
+	}
+	/// One of `CASTES`, picked with chances proportional to `weights`.
+	pub fn pick(weights : &[f32; 3], rng : &mut Rng) -> Self {
+		let mut roll = rng.gen_range(0., weights.iter().sum::<f32>());
+		for (c, w) in CASTES.iter().zip(weights) {
+			if roll < *w { return *c }
+			roll -= w;
+		}
+		Caste::Worker
+	}
+	pub fn write(&self, w : &mut ByteWriter) {
+		w.u8(*self as u8);
+	}
+	pub fn read(r : &mut ByteReader) -> io::Result<Self> {
+		match r.u8()? {
+			t if (t as usize) < CASTES.len() => Ok(CASTES[t as usize]),
+			t => Err(invalid_data(&format!("bad Caste tag {t}"))),
+		}
+	}
+}
+impl AntState {
+	pub fn write(&self, w : &mut ByteWriter) {
+		use AntState::*;
+		match self {
+			Wander(t, a, avel) => { w.u8(0); w.f32(*t); w.f32(*a); w.f32(*avel); },
+			GetFood(id) => { w.u8(1); w.usize(*id); },
+			Follow => w.u8(2),
+			Hungry => w.u8(4),
+			Alarmed(left) => { w.u8(5); w.f32(*left); },
+			GoHome(f, t, a, avel, carried) => {
+				w.u8(3);
+				f.write(w);
+				w.f32(*t); w.f32(*a); w.f32(*avel); w.f32(*carried);
+			},
+		}
+	}
+	pub fn read(r : &mut ByteReader) -> io::Result<Self> {
+		use AntState::*;
+		Ok(match r.u8()? {
+			0 => Wander(r.f32()?, r.f32()?, r.f32()?),
+			1 => GetFood(r.usize()?),
+			2 => Follow,
+			3 => GoHome(Food::read(r)?, r.f32()?, r.f32()?, r.f32()?, r.f32()?),
+			4 => Hungry,
+			5 => Alarmed(r.f32()?),
+			t => return Err(invalid_data(&format!("bad AntState tag {t}"))),
+		})
+	}
 }
@@ -1,16 +1,39 @@
-use std::process::exit;
+use std::{process::exit, fs, io, time::{Duration, Instant}};
 use macroquad::prelude::*;
 use crate::prelude::*;
 
+const SAVE_MAGIC : &[u8; 4] = b"HIVS";
+const SAVE_VERSION : u16 = 18;
+/// Time `TimeScale::Max` may spend stepping each frame, leaving room to render at 60 FPS.
+const MAX_SPEED_FRAME_BUDGET : Duration = Duration::from_millis(12);
+/// How long save and load messages stay up, in seconds.
+const NOTICE_TIME : f32 = 3.;
+
 pub struct Context {
 	signal_queue : SignalQueue,
 	pub scene_manager : SceneManager,
+	input_source : InputSource,
+	pending_input : FrameInput,
+	accumulator : f32,
+	time_scale : TimeScale,
+	resume_time_scale : TimeScale,
+	// recorded frames owed to a replay played back at some speed other than x1
+	playback_budget : f32,
+	/// Message to show and the time it has left on screen.
+	notice : Option<(String, f32)>,
 }
 impl Context {
-	pub fn new(scenes : Vec<Box<dyn Scene>>) -> Self {
+	pub fn new(scenes : Vec<Box<dyn Scene>>, input_source : InputSource) -> Self {
 		Context {
 			signal_queue: SignalQueue::new(),
 			scene_manager: SceneManager::new(scenes),
+			input_source,
+			pending_input: FrameInput::default(),
+			accumulator: 0.,
+			time_scale: TimeScale::Scaled(1.),
+			resume_time_scale: TimeScale::Scaled(1.),
+			playback_budget: 0.,
+			notice: None,
 		}
 	}
 	pub fn init(&mut self, assets : Assets) {
@@ -21,10 +44,176 @@ impl Context {
 		if is_key_pressed(KeyCode::Q) {
 			exit(0);
 		}
-		self.signal_queue.append(self.scene_manager.update());
+		if is_key_pressed(KEY_SAVE) {
+			let msg = match self.save(SAVE_PATH) {
+				Ok(()) => format!("saved to {SAVE_PATH}"),
+				Err(e) => format!("couldn't save: {e}"),
+			};
+			self.notify(msg);
+		}
+		if is_key_pressed(KEY_LOAD) {
+			let msg = match self.input_source {
+				// a load would desync the replay from the inputs it holds
+				InputSource::Live => match self.load(SAVE_PATH) {
+					Ok(()) => format!("loaded {SAVE_PATH}"),
+					Err(e) if e.kind() == io::ErrorKind::NotFound => "no save to load".to_owned(),
+					Err(e) => format!("couldn't load: {e}"),
+				},
+				_ => "can't load during a replay".to_owned(),
+			};
+			self.notify(msg);
+		}
+		if is_key_pressed(KEY_PAUSE) {
+			self.toggle_pause();
+		}
+		if is_key_pressed(KEY_SLOWER) {
+			self.set_time_scale(self.time_scale.slower());
+		}
+		if is_key_pressed(KEY_FASTER) {
+			self.set_time_scale(self.time_scale.faster());
+		}
+
+		if let InputSource::Playback(..) = self.input_source {
+			self.update_playback();
+			return;
+		}
+		let input = FrameInput::poll();
+		match self.time_scale {
+			TimeScale::Paused => self.advance(0., input),
+			TimeScale::Scaled(s) => self.advance(get_frame_time().clamp(0.001, 0.2)*s, input),
+			TimeScale::Max => {
+				let start = Instant::now();
+				let mut input = input;
+				while start.elapsed() < MAX_SPEED_FRAME_BUDGET {
+					self.advance(FIXED_DT, input);
+					input = input.held();
+				}
+			},
+		}
+	}
+	/// Shows `msg` over the game for `NOTICE_TIME` seconds.
+	pub fn notify(&mut self, msg : String) {
+		self.notice = Some((msg, NOTICE_TIME));
+	}
+	pub fn time_scale(&self) -> TimeScale { self.time_scale }
+	pub fn set_time_scale(&mut self, s : TimeScale) {
+		self.time_scale = s;
+	}
+	pub fn toggle_pause(&mut self) {
+		if self.time_scale == TimeScale::Paused {
+			self.time_scale = self.resume_time_scale;
+		} else {
+			self.resume_time_scale = self.time_scale;
+			self.time_scale = TimeScale::Paused;
+		}
+	}
+	/// Consumes recorded frames at the current time scale; the frames themselves
+	/// are fed through unchanged, so the outcome doesn't depend on the speed.
+	fn update_playback(&mut self) {
+		let start = Instant::now();
+		self.playback_budget += match self.time_scale {
+			TimeScale::Paused => 0.,
+			TimeScale::Scaled(s) => s,
+			TimeScale::Max => f32::INFINITY,
+		};
+		while self.playback_budget >= 1. {
+			if self.time_scale == TimeScale::Max && start.elapsed() >= MAX_SPEED_FRAME_BUDGET {
+				break;
+			}
+			let InputSource::Playback(replay, i) = &mut self.input_source else { break };
+			let Some(&(d, input)) = replay.frames.get(*i) else {
+				let msg = format!("replay finished after {i} frames, handing over to the keyboard");
+				self.input_source = InputSource::Live;
+				self.notify(msg);
+				break;
+			};
+			*i += 1;
+			self.playback_budget -= 1.;
+			self.advance(d, input);
+		}
+		if self.playback_budget.is_infinite() {
+			self.playback_budget = 0.;
+		}
+	}
+	/// Adds a frame's worth of (already scaled) time and runs every fixed step
+	/// that fits. When recording, this is exactly what ends up in the replay.
+	fn advance(&mut self, d : f32, input : FrameInput) {
+		if let InputSource::Recording(recorder) = &mut self.input_source {
+			recorder.record(d, &input).expect("Failed to write replay frame");
+		}
+		self.pending_input = self.pending_input.merge(&input);
+		self.accumulator += d;
+		while self.accumulator >= FIXED_DT {
+			self.accumulator -= FIXED_DT;
+			let input = self.pending_input;
+			self.pending_input = input.held();
+			self.signal_queue.append(self.scene_manager.update(FIXED_DT, &input));
+			// a scene switch has to land before the next step, not after the frame
+			self.handle_signals();
+		}
+	}
+	/// Layout: magic, version `u16`, active scene index, then every scene's
+	/// `write_state` in order.
+	pub fn save(&self, path : &str) -> io::Result<()> {
+		let mut w = ByteWriter::new();
+		w.raw(SAVE_MAGIC);
+		w.u16(SAVE_VERSION);
+		w.usize(self.scene_manager.get_active_index());
+		for s in self.scene_manager.scenes.iter() {
+			s.write_state(&mut w);
+		}
+		// write then rename, so a crash mid-save can't clobber the last good one
+		let tmp = format!("{path}.tmp");
+		fs::write(&tmp, &w.bytes)?;
+		fs::rename(&tmp, path)
+	}
+	pub fn load(&mut self, path : &str) -> io::Result<()> {
+		let bytes = fs::read(path)?;
+		let mut r = ByteReader::new(&bytes);
+		if r.raw(SAVE_MAGIC.len()).ok() != Some(SAVE_MAGIC) {
+			return Err(invalid_data("not a save file"));
+		}
+		let version = r.u16()?;
+		if version != SAVE_VERSION {
+			return Err(invalid_data(&format!("unsupported save version {version}")));
+		}
+		let active = r.usize()?;
+		if active >= self.scene_manager.scenes.len() {
+			return Err(invalid_data(&format!("no scene with index {active}")));
+		}
+		// scenes can only be read into in place, so keep a scratch copy of how
+		// they are now and put it back if the file turns out bad partway through
+		let mut scratch = ByteWriter::new();
+		for s in self.scene_manager.scenes.iter() {
+			s.write_state(&mut scratch);
+		}
+		let read = self.scene_manager.scenes.iter_mut()
+			.try_for_each(|s| s.read_state(&mut r));
+		if let Err(e) = read {
+			let mut r = ByteReader::new(&scratch.bytes);
+			for s in self.scene_manager.scenes.iter_mut() {
+				s.read_state(&mut r).expect("Failed to restore scenes after a bad load");
+			}
+			return Err(e);
+		}
+		self.scene_manager.restore_active_scene(active);
+		self.signal_queue.signals.clear();
+		Ok(())
 	}
 	pub fn render(&mut self) {
-		self.signal_queue.append(self.scene_manager.render());
+		let alpha = self.accumulator / FIXED_DT;
+		self.signal_queue.append(self.scene_manager.render(alpha));
+		let scale = self.time_scale();
+		if scale != TimeScale::Scaled(1.) {
+			quick_text(&scale.label(), vec2(2., H-2.), WHITE);
+		}
+		if let Some((msg, left)) = &mut self.notice {
+			quick_text(msg, vec2(2., H-10.), WHITE);
+			*left -= get_frame_time();
+			if *left <= 0. {
+				self.notice = None;
+			}
+		}
 	}
 	pub fn handle_signals(&mut self) {
 		loop {
@@ -33,7 +222,57 @@ impl Context {
 			use Signal::*;
 			match s.unwrap() {
 				SetScene(i) => self.scene_manager.set_active_scene(i),
+				Restart => self.scene_manager.restart(),
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// Holds one byte, and refuses to read anything above `max`.
+	struct Counter(u8, u8);
+	impl Scene for Counter {
+		fn update(&mut self, _q : &mut SignalQueue, _d : f32, _input : &FrameInput) {}
+		fn render(&mut self, _q : &mut SignalQueue, _alpha : f32) {}
+		fn write_state(&self, w : &mut ByteWriter) { w.u8(self.0); }
+		fn read_state(&mut self, r : &mut ByteReader) -> io::Result<()> {
+			match r.u8()? {
+				v if v <= self.1 => { self.0 = v; Ok(()) },
+				v => Err(invalid_data(&format!("{v} is too big"))),
 			}
 		}
 	}
+	fn values(ctx : &Context) -> Vec<u8> {
+		let mut w = ByteWriter::new();
+		for s in ctx.scene_manager.scenes.iter() {
+			s.write_state(&mut w);
+		}
+		w.bytes
+	}
+
+	#[test]
+	fn bad_load_leaves_scenes_alone() {
+		let path = std::env::temp_dir().join("hive_bad_load_test.sav");
+		let path = path.to_str().unwrap();
+		let mut ctx = Context::new(vec![Box::new(Counter(1, 9)), Box::new(Counter(2, 9))], InputSource::Live);
+		ctx.save(path).unwrap();
+
+		// the first scene reads fine, the second is out of range
+		let mut bytes = fs::read(path).unwrap();
+		let n = bytes.len();
+		bytes[n-2] = 5;
+		bytes[n-1] = 200;
+		fs::write(path, &bytes).unwrap();
+		assert!(ctx.load(path).is_err());
+		assert_eq!(values(&ctx), [1, 2]);
+
+		bytes[n-1] = 7;
+		fs::write(path, &bytes).unwrap();
+		ctx.load(path).unwrap();
+		assert_eq!(values(&ctx), [5, 7]);
+		fs::remove_file(path).unwrap();
+	}
 }
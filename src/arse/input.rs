@@ -25,10 +25,42 @@ pub fn get_iv() -> Vec2 {
 			is_key_down(KeyCode::Up)  { 1. } else { 0. },
 	)
 }
-pub fn get_ivn() -> Vec2 {
-	let iv = get_iv();
-	match iv.length().floor() as i32 {
-		0 => Vec2::ZERO,
-		_ => iv.normalize(),
+
+/// Everything the simulation reads from the player in a single step.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct FrameInput {
+	pub iv : Vec2,
+	pub toggle_marker : bool,
+	pub restart : bool,
+	pub alarm : bool,
+}
+impl FrameInput {
+	pub fn poll() -> Self {
+		FrameInput {
+			iv: get_iv(),
+			toggle_marker: is_key_pressed(KeyCode::Space),
+			restart: is_key_pressed(KeyCode::R),
+			alarm: is_key_pressed(KeyCode::E),
+		}
+	}
+	/// Combines with the next poll, keeping presses from either so none are
+	/// lost on frames too short to run a step.
+	pub fn merge(&self, next : &FrameInput) -> Self {
+		FrameInput {
+			iv: next.iv,
+			toggle_marker: self.toggle_marker || next.toggle_marker,
+			restart: self.restart || next.restart,
+			alarm: self.alarm || next.alarm,
+		}
+	}
+	/// Only what's held down, for the steps after the one a press was handled in.
+	pub fn held(&self) -> Self {
+		FrameInput { iv: self.iv, ..FrameInput::default() }
+	}
+	pub fn ivn(&self) -> Vec2 {
+		match self.iv.length().floor() as i32 {
+			0 => Vec2::ZERO,
+			_ => self.iv.normalize(),
+		}
 	}
 }
@@ -1,6 +1,6 @@
 use std::f32::consts::PI;
 
-use macroquad::{prelude::{Vec2, vec2}, rand};
+use macroquad::prelude::{Vec2, vec2};
 
 pub fn lerp<T:
 	Copy +
@@ -16,14 +16,93 @@ pub fn angle_lerp(a : f32, b : f32, t : f32) -> f32 {
 	else { lerp(a, b, t) }
 }
 
-pub fn random_angle() -> f32 {
-	rand::gen_range(0., PI*2.0)
+const RNG_MULTIPLIER : u64 = 6364136223846793005;
+const RNG_INCREMENT : u64 = 1442695040888963407;
+/// PCG32, same as `macroquad::rand` uses internally, but owned instead of global
+/// so a simulation can be reproduced from its seed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rng {
+	state : u64,
+}
+impl Rng {
+	pub fn new(seed : u64) -> Self {
+		let mut rng = Rng { state: 0 };
+		rng.next_u32();
+		rng.state = rng.state.wrapping_add(seed);
+		rng.next_u32();
+		rng
+	}
+	pub fn from_state(state : u64) -> Self { Rng { state } }
+	pub fn state(&self) -> u64 { self.state }
+	pub fn next_u32(&mut self) -> u32 {
+		let old = self.state;
+		self.state = old.wrapping_mul(RNG_MULTIPLIER).wrapping_add(RNG_INCREMENT);
+		let xorshifted = (((old >> 18) ^ old) >> 27) as u32;
+		xorshifted.rotate_right((old >> 59) as u32)
+	}
+	/// Uniform in `[low, high)`.
+	pub fn gen_range<T : RandomRange>(&mut self, low : T, high : T) -> T {
+		T::gen_range(self, low, high)
+	}
+}
+pub trait RandomRange {
+	fn gen_range(rng : &mut Rng, low : Self, high : Self) -> Self;
+}
+macro_rules! impl_random_range {
+	($($t:ty),*) => { $(
+		impl RandomRange for $t {
+			fn gen_range(rng : &mut Rng, low : Self, high : Self) -> Self {
+				let r = rng.next_u32() as f64 / (u32::MAX as f64 + 1.0);
+				let v = (low as f64 + (high as f64 - low as f64) * r) as $t;
+				// narrowing to f32 can round up onto `high`
+				if v < high { v } else { low }
+			}
+		}
+	)* }
+}
+impl_random_range!(f32, i32, usize);
+
+pub fn random_angle(rng : &mut Rng) -> f32 {
+	rng.gen_range(0., PI*2.0)
 }
 pub fn random_ring_point(
 	origin : &Vec2,
 	min_dist : f32,
-	max_dist : f32) -> Vec2 {
-	let a = random_angle();
-	let d = rand::gen_range(min_dist, max_dist);
+	max_dist : f32,
+	rng : &mut Rng) -> Vec2 {
+	let a = random_angle(rng);
+	let d = rng.gen_range(min_dist, max_dist);
 	*origin + vec2(a.cos(), a.sin())*d
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn gen_range_in_bounds() {
+		let mut rng = Rng::new(7);
+		for _ in 0..10000 {
+			let f = rng.gen_range(-2.5, 4.);
+			assert!((-2.5..4.).contains(&f), "{f} out of range");
+			let i = rng.gen_range(-3, 3);
+			assert!((-3..3).contains(&i), "{i} out of range");
+			let u = rng.gen_range(1, 4);
+			assert!((1..4).contains(&u), "{u} out of range");
+		}
+		// a state whose next draw is u32::MAX, the closest `gen_range` gets to `high`
+		const TOP : u64 = 0x07ff_fe00_0000_0000;
+		assert_eq!(Rng::from_state(TOP).next_u32(), u32::MAX);
+		assert!(Rng::from_state(TOP).gen_range(-2.5, 4.) < 4.);
+		assert!(Rng::from_state(TOP).gen_range(0., 1.) < 1.);
+		assert_eq!(Rng::from_state(TOP).gen_range(-3, 3), 2);
+	}
+	#[test]
+	fn same_seed_same_numbers() {
+		let (mut a, mut b) = (Rng::new(42), Rng::new(42));
+		for _ in 0..100 {
+			assert_eq!(a.next_u32(), b.next_u32());
+		}
+		assert_ne!(Rng::new(1).next_u32(), Rng::new(2).next_u32());
+	}
+}
@@ -3,3 +3,7 @@ pub mod scenes;
 pub mod rendering;
 pub mod context;
 pub mod input;
+pub mod replay;
+pub mod serial;
+pub mod time;
+pub mod spatial;
@@ -0,0 +1,96 @@
+use std::{fs::File, io::{self, Read, Write}};
+
+use macroquad::prelude::vec2;
+use crate::prelude::{FrameInput, ByteReader, ByteWriter, invalid_data};
+use crate::world::TrailBackend;
+
+const REPLAY_MAGIC : &[u8; 4] = b"HIVR";
+const REPLAY_VERSION : u8 = 2;
+const FRAME_LEN : usize = 4 + 1;
+
+/// Every input the simulation saw, one entry per `Context::update`,
+/// plus the seed and trail backend it started from.
+///
+/// Layout (little endian): magic, version, seed `u64`, trail backend `u8`,
+/// then per frame the `f32` dt and one byte packing the input (see `pack_input`).
+#[derive(Debug, Clone)]
+pub struct Replay {
+	pub seed : u64,
+	pub trails : TrailBackend,
+	pub frames : Vec<(f32, FrameInput)>,
+}
+impl Replay {
+	pub fn load(path : &str) -> io::Result<Self> {
+		let mut bytes = Vec::new();
+		File::open(path)?.read_to_end(&mut bytes)?;
+		Self::from_bytes(&bytes)
+	}
+	pub fn from_bytes(bytes : &[u8]) -> io::Result<Self> {
+		let mut r = ByteReader::new(bytes);
+		if r.raw(REPLAY_MAGIC.len()).ok() != Some(REPLAY_MAGIC) {
+			return Err(invalid_data("not a replay file"));
+		}
+		let version = r.u8()?;
+		if version != REPLAY_VERSION {
+			return Err(invalid_data(&format!("unsupported replay version {version}")));
+		}
+		let seed = r.u64()?;
+		let trails = TrailBackend::read(&mut r)?;
+		let mut frames = Vec::new();
+		// a trailing partial frame means the recording was cut off mid-write; drop it
+		while r.remaining() >= FRAME_LEN {
+			frames.push((r.f32()?, unpack_input(r.u8()?)));
+		}
+		Ok(Replay { seed, trails, frames })
+	}
+}
+
+/// Streams frames to disk as they happen, so a session killed by closing
+/// the window still leaves a usable replay behind.
+pub struct ReplayRecorder {
+	file : File,
+}
+impl ReplayRecorder {
+	pub fn create(path : &str, seed : u64, trails : TrailBackend) -> io::Result<Self> {
+		let mut w = ByteWriter::new();
+		w.raw(REPLAY_MAGIC);
+		w.u8(REPLAY_VERSION);
+		w.u64(seed);
+		trails.write(&mut w);
+		let mut file = File::create(path)?;
+		file.write_all(&w.bytes)?;
+		Ok(ReplayRecorder { file })
+	}
+	pub fn record(&mut self, d : f32, input : &FrameInput) -> io::Result<()> {
+		let mut w = ByteWriter::new();
+		w.f32(d);
+		w.u8(pack_input(input));
+		self.file.write_all(&w.bytes)
+	}
+}
+
+/// Where `Context` gets each frame's dt and input from.
+pub enum InputSource {
+	Live,
+	Recording(ReplayRecorder),
+	Playback(Replay, usize),
+}
+
+// movement keys are digital, so each axis of `iv` is one of -1, 0, 1
+fn pack_input(input : &FrameInput) -> u8 {
+	let axis = |v : f32| (v.round().clamp(-1., 1.) as i8 + 1) as u8;
+	axis(input.iv.x)
+		| axis(input.iv.y) << 2
+		| (input.toggle_marker as u8) << 4
+		| (input.restart as u8) << 5
+		| (input.alarm as u8) << 6
+}
+fn unpack_input(b : u8) -> FrameInput {
+	let axis = |bits : u8| (bits & 0b11) as f32 - 1.;
+	FrameInput {
+		iv: vec2(axis(b), axis(b >> 2)),
+		toggle_marker: b & (1 << 4) != 0,
+		restart: b & (1 << 5) != 0,
+		alarm: b & (1 << 6) != 0,
+	}
+}
@@ -1,13 +1,24 @@
+use std::io;
+
 use crate::SignalQueue;
 use crate::Assets;
+use crate::FrameInput;
+use crate::{ByteWriter, ByteReader};
 
 #[allow(unused_variables)]
 pub trait Scene {
 	fn load(&mut self) {}
 	fn unload(&mut self) {}
 	fn init(&mut self, a : &Assets) {}
-	fn update(&mut self, q : &mut SignalQueue);
-	fn render(&mut self, q : &mut SignalQueue);
+	/// Start a new run from scratch; every scene gets this on `Signal::Restart`.
+	fn restart(&mut self) {}
+	fn update(&mut self, q : &mut SignalQueue, d : f32, input : &FrameInput);
+	/// Runs instead of `update` while some other scene is active.
+	fn background_update(&mut self, d : f32) {}
+	/// `alpha` is how far into the next update this frame falls, for interpolation.
+	fn render(&mut self, q : &mut SignalQueue, alpha : f32);
+	fn write_state(&self, w : &mut ByteWriter) {}
+	fn read_state(&mut self, r : &mut ByteReader) -> io::Result<()> { Ok(()) }
 }
 pub struct SceneManager {
 	pub scenes : Vec<Box<dyn Scene>>,
@@ -34,20 +45,37 @@ impl SceneManager {
 		self.scenes[self.active_index].load();
 	}
 
+	/// Switches scenes without calling `unload`/`load`, for restoring a save
+	/// where the incoming scene's state is already exactly as it should be.
+	pub fn restore_active_scene(&mut self, i : usize) {
+		self.active_index = i;
+	}
+
+	pub fn restart(&mut self) {
+		for s in self.scenes.iter_mut() {
+			s.restart()
+		}
+	}
+
 	pub fn init(&mut self, assets : &Assets) {
 		for s in self.scenes.iter_mut() {
 			s.init(assets)
 		}
 		self.scenes[self.active_index].load();
 	}
-	pub fn update(&mut self) -> SignalQueue {
+	pub fn update(&mut self, d : f32, input : &FrameInput) -> SignalQueue {
 		let mut q = SignalQueue::new();
-		self.get_active_scene_mut().update(&mut q);
+		for (i, s) in self.scenes.iter_mut().enumerate() {
+			if i != self.active_index {
+				s.background_update(d);
+			}
+		}
+		self.get_active_scene_mut().update(&mut q, d, input);
 		q
 	}
-	pub fn render(&mut self) -> SignalQueue {
+	pub fn render(&mut self, alpha : f32) -> SignalQueue {
 		let mut q = SignalQueue::new();
-		self.get_active_scene_mut().render(&mut q);
+		self.get_active_scene_mut().render(&mut q, alpha);
 		q
 	}
 }
@@ -0,0 +1,80 @@
+use std::io;
+
+use macroquad::prelude::{Vec2, vec2, Color};
+
+/// Little endian byte sink for replays and save files.
+#[derive(Debug, Default)]
+pub struct ByteWriter {
+	pub bytes : Vec<u8>,
+}
+#[allow(dead_code)]
+impl ByteWriter {
+	pub fn new() -> Self { ByteWriter { bytes: Vec::new() } }
+	pub fn raw(&mut self, b : &[u8]) { self.bytes.extend_from_slice(b); }
+	pub fn u8(&mut self, v : u8) { self.bytes.push(v); }
+	pub fn u16(&mut self, v : u16) { self.raw(&v.to_le_bytes()); }
+	pub fn u64(&mut self, v : u64) { self.raw(&v.to_le_bytes()); }
+	pub fn i32(&mut self, v : i32) { self.raw(&v.to_le_bytes()); }
+	pub fn usize(&mut self, v : usize) { self.u64(v as u64); }
+	pub fn f32(&mut self, v : f32) { self.raw(&v.to_le_bytes()); }
+	pub fn bool(&mut self, v : bool) { self.u8(v as u8); }
+	pub fn vec2(&mut self, v : &Vec2) { self.f32(v.x); self.f32(v.y); }
+	pub fn color(&mut self, c : &Color) {
+		self.f32(c.r); self.f32(c.g); self.f32(c.b); self.f32(c.a);
+	}
+	pub fn option<T>(&mut self, v : &Option<T>, f : impl FnOnce(&mut Self, &T)) {
+		self.bool(v.is_some());
+		if let Some(v) = v { f(self, v); }
+	}
+	pub fn seq<T>(&mut self, v : &[T], mut f : impl FnMut(&mut Self, &T)) {
+		self.usize(v.len());
+		for x in v { f(self, x); }
+	}
+}
+
+/// Counterpart of `ByteWriter`. Running off the end or hitting a bad tag
+/// is an `InvalidData` error rather than a panic, since the bytes come from disk.
+pub struct ByteReader<'a> {
+	bytes : &'a [u8],
+	at : usize,
+}
+#[allow(dead_code)]
+impl<'a> ByteReader<'a> {
+	pub fn new(bytes : &'a [u8]) -> Self { ByteReader { bytes, at: 0 } }
+	pub fn remaining(&self) -> usize { self.bytes.len() - self.at }
+	pub fn raw(&mut self, n : usize) -> io::Result<&'a [u8]> {
+		if self.remaining() < n {
+			return Err(invalid_data("unexpected end of data"));
+		}
+		self.at += n;
+		Ok(&self.bytes[self.at-n..self.at])
+	}
+	fn array<const N : usize>(&mut self) -> io::Result<[u8; N]> {
+		Ok(self.raw(N)?.try_into().unwrap())
+	}
+	pub fn u8(&mut self) -> io::Result<u8> { Ok(self.raw(1)?[0]) }
+	pub fn u16(&mut self) -> io::Result<u16> { Ok(u16::from_le_bytes(self.array()?)) }
+	pub fn u64(&mut self) -> io::Result<u64> { Ok(u64::from_le_bytes(self.array()?)) }
+	pub fn i32(&mut self) -> io::Result<i32> { Ok(i32::from_le_bytes(self.array()?)) }
+	pub fn usize(&mut self) -> io::Result<usize> { Ok(self.u64()? as usize) }
+	pub fn f32(&mut self) -> io::Result<f32> { Ok(f32::from_le_bytes(self.array()?)) }
+	pub fn bool(&mut self) -> io::Result<bool> { Ok(self.u8()? != 0) }
+	pub fn vec2(&mut self) -> io::Result<Vec2> { Ok(vec2(self.f32()?, self.f32()?)) }
+	pub fn color(&mut self) -> io::Result<Color> {
+		Ok(Color::new(self.f32()?, self.f32()?, self.f32()?, self.f32()?))
+	}
+	pub fn option<T>(&mut self, f : impl FnOnce(&mut Self) -> io::Result<T>) -> io::Result<Option<T>> {
+		Ok(if self.bool()? { Some(f(self)?) } else { None })
+	}
+	pub fn seq<T>(&mut self, mut f : impl FnMut(&mut Self) -> io::Result<T>) -> io::Result<Vec<T>> {
+		let n = self.usize()?;
+		if n > self.remaining() {
+			return Err(invalid_data("sequence longer than the data holding it"));
+		}
+		(0..n).map(|_| f(self)).collect()
+	}
+}
+
+pub fn invalid_data(msg : &str) -> io::Error {
+	io::Error::new(io::ErrorKind::InvalidData, msg.to_owned())
+}
@@ -0,0 +1,236 @@
+use std::collections::HashMap;
+
+use macroquad::prelude::{Vec2, vec2};
+
+pub type CellKey = (i32, i32);
+
+/// Buckets items into square cells so queries only look at the cells
+/// their area overlaps. Queries visit cells row by row and items in
+/// insertion order within a cell, so their results come out in a stable order.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpatialGrid<T> {
+	cell_size : f32,
+	cells : HashMap<CellKey, Vec<(Vec2, T)>>,
+	len : usize,
+}
+#[allow(dead_code)]
+impl<T> SpatialGrid<T> {
+	pub fn new(cell_size : f32) -> Self {
+		SpatialGrid {
+			cell_size,
+			cells: HashMap::new(),
+			len: 0,
+		}
+	}
+	pub fn cell_size(&self) -> f32 { self.cell_size }
+	pub fn len(&self) -> usize { self.len }
+	pub fn is_empty(&self) -> bool { self.len == 0 }
+	pub fn key(&self, pos : &Vec2) -> CellKey {
+		let key = *pos / self.cell_size;
+		(key.x.floor() as i32, key.y.floor() as i32)
+	}
+	pub fn cell_pos(&self, key : &CellKey) -> Vec2 {
+		vec2(key.0 as f32, key.1 as f32) * self.cell_size
+	}
+
+	pub fn insert(&mut self, pos : Vec2, item : T) {
+		let key = self.key(&pos);
+		self.cells.entry(key).or_default().push((pos, item));
+		self.len += 1;
+	}
+	/// Removes the first item in `pos`'s cell that `pred` accepts.
+	pub fn remove(&mut self, pos : &Vec2, pred : impl Fn(&T) -> bool) -> Option<(Vec2, T)> {
+		let key = self.key(pos);
+		let cell = self.cells.get_mut(&key)?;
+		let i = cell.iter().position(|(_, t)| pred(t))?;
+		let out = cell.remove(i);
+		if cell.is_empty() {
+			self.cells.remove(&key);
+		}
+		self.len -= 1;
+		Some(out)
+	}
+	/// Moves the first item in `from`'s cell that `pred` accepts to `to`.
+	/// Returns false if there was no such item.
+	pub fn move_item(&mut self, from : &Vec2, to : Vec2, pred : impl Fn(&T) -> bool) -> bool {
+		if self.key(from) == self.key(&to) {
+			let Some(cell) = self.cells.get_mut(&self.key(from)) else { return false };
+			match cell.iter_mut().find(|(_, t)| pred(t)) {
+				Some(entry) => { entry.0 = to; true },
+				None => false,
+			}
+		} else {
+			match self.remove(from, pred) {
+				Some((_, item)) => { self.insert(to, item); true },
+				None => false,
+			}
+		}
+	}
+	pub fn clear(&mut self) {
+		self.cells.clear();
+		self.len = 0;
+	}
+	pub fn retain(&mut self, mut f : impl FnMut(&Vec2, &mut T) -> bool) {
+		let mut len = 0;
+		self.cells.retain(|_, cell| {
+			cell.retain_mut(|(p, t)| f(p, t));
+			len += cell.len();
+			!cell.is_empty()
+		});
+		self.len = len;
+	}
+
+	pub fn cell(&self, key : &CellKey) -> &[(Vec2, T)] {
+		self.cells.get(key).map(|c| c.as_slice()).unwrap_or(&[])
+	}
+	pub fn cell_mut(&mut self, key : &CellKey) -> &mut [(Vec2, T)] {
+		self.cells.get_mut(key).map(|c| c.as_mut_slice()).unwrap_or(&mut [])
+	}
+	/// Occupied cells, sorted by key.
+	pub fn cells(&self) -> Vec<(&CellKey, &Vec<(Vec2, T)>)> {
+		let mut out : Vec<_> = self.cells.iter().collect();
+		out.sort_by_key(|(k, _)| **k);
+		out
+	}
+	pub fn iter(&self) -> impl Iterator<Item = &(Vec2, T)> {
+		self.cells.values().flatten()
+	}
+	pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut (Vec2, T)> {
+		self.cells.values_mut().flatten()
+	}
+
+	fn cells_in_range(&self, min : CellKey, max : CellKey) -> impl Iterator<Item = &(Vec2, T)> {
+		(min.1..=max.1)
+			.flat_map(move |y| (min.0..=max.0).map(move |x| (x, y)))
+			.flat_map(|k| self.cell(&k).iter())
+	}
+	pub fn query_aabb(&self, min : &Vec2, max : &Vec2) -> impl Iterator<Item = &(Vec2, T)> {
+		let (min, max) = (*min, *max);
+		self.cells_in_range(self.key(&min), self.key(&max))
+			.filter(move |(p, _)| p.x >= min.x && p.y >= min.y && p.x <= max.x && p.y <= max.y)
+	}
+	pub fn query_radius(&self, center : &Vec2, radius : f32) -> impl Iterator<Item = &(Vec2, T)> {
+		let center = *center;
+		let r = Vec2::splat(radius);
+		self.cells_in_range(self.key(&(center - r)), self.key(&(center + r)))
+			.filter(move |(p, _)| p.distance_squared(center) <= radius*radius)
+	}
+	/// Up to `k` items within `max_radius`, closest first. Searches outward ring
+	/// by ring, stopping once no unvisited cell could hold anything closer.
+	pub fn k_nearest(&self, center : &Vec2, k : usize, max_radius : f32) -> Vec<&(Vec2, T)> {
+		let mut found : Vec<(f32, &(Vec2, T))> = Vec::new();
+		if k == 0 { return Vec::new() }
+		let c = self.key(center);
+		let max_ring = (max_radius / self.cell_size).ceil() as i32 + 1;
+		for ring in 0..=max_ring {
+			for y in (c.1-ring)..=(c.1+ring) {
+				for x in (c.0-ring)..=(c.0+ring) {
+					if (x - c.0).abs() != ring && (y - c.1).abs() != ring { continue }
+					for e in self.cell(&(x, y)) {
+						let d = e.0.distance(*center);
+						if d <= max_radius {
+							found.push((d, e));
+						}
+					}
+				}
+			}
+			found.sort_by(|a, b| a.0.total_cmp(&b.0));
+			found.truncate(k);
+			// everything past this ring is at least `ring` whole cells away
+			if found.len() == k && found[k-1].0 <= ring as f32 * self.cell_size {
+				break;
+			}
+		}
+		found.into_iter().map(|(_, e)| e).collect()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::prelude::{Rng, random_ring_point};
+
+	const CELL : f32 = 10.;
+
+	/// Points scattered around the origin, plus some right on cell edges.
+	fn scattered() -> (SpatialGrid<usize>, Vec<Vec2>) {
+		let mut rng = Rng::new(3);
+		let mut points : Vec<Vec2> = (0..200)
+			.map(|_| random_ring_point(&Vec2::ZERO, 0., CELL*5.5, &mut rng))
+			.collect();
+		for x in -3..=3 {
+			for y in -3..=3 {
+				points.push(vec2(x as f32, y as f32)*CELL);
+			}
+		}
+		let mut grid = SpatialGrid::new(CELL);
+		for (i, p) in points.iter().enumerate() {
+			grid.insert(*p, i);
+		}
+		(grid, points)
+	}
+	fn sorted<'a>(found : impl Iterator<Item = &'a (Vec2, usize)>) -> Vec<usize> {
+		let mut ids : Vec<usize> = found.map(|(_, i)| *i).collect();
+		ids.sort();
+		ids
+	}
+
+	#[test]
+	fn edges_and_negatives() {
+		let grid = SpatialGrid::<()>::new(CELL);
+		assert_eq!(grid.key(&vec2(CELL, 0.)), (1, 0));
+		assert_eq!(grid.key(&vec2(CELL - 0.01, 0.)), (0, 0));
+		assert_eq!(grid.key(&vec2(-0.01, -CELL)), (-1, -1));
+		assert_eq!(grid.key(&vec2(-CELL - 0.01, 0.)), (-2, 0));
+
+		let (grid, points) = scattered();
+		for (i, p) in points.iter().enumerate() {
+			// a zero sized query still finds a point exactly on an edge
+			assert!(grid.query_radius(p, 0.).any(|(_, j)| *j == i));
+			assert!(grid.query_aabb(p, p).any(|(_, j)| *j == i));
+		}
+	}
+	#[test]
+	fn queries_match_brute_force() {
+		let (grid, points) = scattered();
+		let centers = [vec2(0., 0.), vec2(-CELL, -CELL), vec2(-23., 17.), vec2(CELL*2., -CELL*0.5), vec2(-60., -60.)];
+		for c in centers {
+			for r in [0., CELL*0.5, CELL, CELL*2.5, CELL*4.7] {
+				let expected : Vec<usize> = (0..points.len())
+					.filter(|i| points[*i].distance_squared(c) <= r*r)
+					.collect();
+				assert_eq!(sorted(grid.query_radius(&c, r)), expected, "radius {r} around {c}");
+
+				let (min, max) = (c - Vec2::splat(r), c + Vec2::splat(r));
+				let expected : Vec<usize> = (0..points.len())
+					.filter(|i| points[*i].cmpge(min).all() && points[*i].cmple(max).all())
+					.collect();
+				assert_eq!(sorted(grid.query_aabb(&min, &max)), expected, "box {min} to {max}");
+
+				let mut by_dist : Vec<f32> = points.iter()
+					.map(|p| p.distance(c))
+					.filter(|d| *d <= r)
+					.collect();
+				by_dist.sort_by(f32::total_cmp);
+				by_dist.truncate(5);
+				let nearest : Vec<f32> = grid.k_nearest(&c, 5, r).iter().map(|(p, _)| p.distance(c)).collect();
+				assert_eq!(nearest, by_dist, "5 nearest within {r} of {c}");
+			}
+		}
+	}
+	#[test]
+	fn retain_and_cell_mut() {
+		let (mut grid, points) = scattered();
+		grid.retain(|p, _| p.x >= 0.);
+		assert_eq!(grid.len(), points.iter().filter(|p| p.x >= 0.).count());
+		assert_eq!(grid.iter().count(), grid.len());
+		assert!(grid.cells().iter().all(|(k, v)| k.0 >= 0 && !v.is_empty()));
+
+		let key = grid.key(&vec2(CELL, CELL));
+		for (_, i) in grid.cell_mut(&key) {
+			*i += 1000;
+		}
+		assert!(grid.cell(&key).iter().all(|(_, i)| *i >= 1000));
+		assert!(grid.cell_mut(&(-1, 0)).is_empty());
+	}
+}
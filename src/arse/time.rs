@@ -0,0 +1,28 @@
+/// How fast simulated time runs compared to real time.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TimeScale {
+	Paused,
+	Scaled(f32),
+	/// As many fixed steps as fit in the frame budget.
+	Max,
+}
+use TimeScale::*;
+pub const TIME_SCALES : &[TimeScale] = &[Paused, Scaled(0.5), Scaled(1.), Scaled(2.), Scaled(4.), Max];
+impl TimeScale {
+	fn index(&self) -> usize {
+		TIME_SCALES.iter().position(|s| s == self).unwrap_or(2)
+	}
+	pub fn faster(&self) -> Self {
+		TIME_SCALES[(self.index()+1).min(TIME_SCALES.len()-1)]
+	}
+	pub fn slower(&self) -> Self {
+		TIME_SCALES[self.index().saturating_sub(1)]
+	}
+	pub fn label(&self) -> String {
+		match self {
+			Paused => "PAUSED".to_owned(),
+			Scaled(s) => format!("x{s}"),
+			Max => "MAX".to_owned(),
+		}
+	}
+}
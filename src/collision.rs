@@ -0,0 +1,303 @@
+use macroquad::{prelude::{Vec2, vec2}, texture::Image};
+
+/// Image pixels per collision cell.
+pub const COLLISION_GRID_RESOLUTION : f32 = 4.;
+/// Alpha above which a pixel of the collision image counts as wall.
+const COLLISION_ALPHA : f32 = 0.29;
+
+/// Solid/free grid over the map, one bit per cell. Anything outside the
+/// grid counts as solid.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CollisionMap {
+	tl : Vec2,
+	cell_size : Vec2,
+	w : i32,
+	h : i32,
+	bits : Vec<u64>,
+}
+#[allow(dead_code)]
+impl CollisionMap {
+	pub fn new(tl : Vec2) -> Self {
+		CollisionMap {
+			tl,
+			cell_size: Vec2::ONE,
+			w: 0,
+			h: 0,
+			bits: Vec::new(),
+		}
+	}
+	pub fn from_image(col_map : &Image, tl : Vec2, map_dims : &Vec2) -> Self {
+		let w = (col_map.width() as f32/COLLISION_GRID_RESOLUTION) as i32;
+		let h = (col_map.height() as f32/COLLISION_GRID_RESOLUTION) as i32;
+		let mut map = CollisionMap {
+			tl,
+			cell_size: *map_dims / vec2(w as f32, h as f32),
+			w,
+			h,
+			bits: vec![0; ((w*h) as usize).div_ceil(64)],
+		};
+		for y in 0..h {
+			for x in 0..w {
+				let solid = col_map.get_pixel(
+					((x as f32 + 0.5) * COLLISION_GRID_RESOLUTION) as u32,
+					((y as f32 + 0.5) * COLLISION_GRID_RESOLUTION) as u32
+				).a > COLLISION_ALPHA;
+				if solid {
+					let i = (y*w + x) as usize;
+					map.bits[i/64] |= 1 << (i%64);
+				}
+			}
+		}
+		map
+	}
+	pub fn cell_size(&self) -> Vec2 { self.cell_size }
+	/// Grid size in cells.
+	pub fn dims(&self) -> (i32, i32) { (self.w, self.h) }
+	/// Position of a cell in a flat `w*h` array, if it's on the grid.
+	pub fn index(&self, x : i32, y : i32) -> Option<usize> {
+		if x < 0 || y < 0 || x >= self.w || y >= self.h { return None }
+		Some((y*self.w + x) as usize)
+	}
+	pub fn cell_of(&self, pos : &Vec2) -> (i32, i32) {
+		let g = self.to_grid(pos);
+		(g.x.floor() as i32, g.y.floor() as i32)
+	}
+	pub fn cell_centre(&self, x : i32, y : i32) -> Vec2 {
+		self.tl + (vec2(x as f32, y as f32) + Vec2::splat(0.5))*self.cell_size
+	}
+	fn to_grid(&self, pos : &Vec2) -> Vec2 {
+		(*pos - self.tl)/self.cell_size
+	}
+	pub fn is_solid(&self, x : i32, y : i32) -> bool {
+		match self.index(x, y) {
+			Some(i) => self.bits[i/64] >> (i%64) & 1 == 1,
+			None => true,
+		}
+	}
+	pub fn is_collision(&self, pos : &Vec2) -> bool {
+		let (x, y) = self.cell_of(pos);
+		self.is_solid(x, y)
+	}
+	/// Every cell with whether it's solid, for debug views.
+	pub fn cells(&self) -> impl Iterator<Item = ((i32, i32), bool)> + '_ {
+		(0..self.h)
+			.flat_map(move |y| (0..self.w).map(move |x| (x, y)))
+			.map(|(x, y)| ((x, y), self.is_solid(x, y)))
+	}
+
+	/// First point where a ray from `from` along `dir` enters a solid cell,
+	/// within `max_dist`. Walks the grid cell by cell, so it can't skip thin walls.
+	pub fn raycast(&self, from : &Vec2, dir : &Vec2, max_dist : f32) -> Option<Vec2> {
+		let to = *from + dir.normalize_or_zero()*max_dist;
+		let (a, b) = (self.to_grid(from), self.to_grid(&to));
+		let d = b - a;
+		let (mut x, mut y) = (a.x.floor() as i32, a.y.floor() as i32);
+		let end = (b.x.floor() as i32, b.y.floor() as i32);
+		let (step_x, step_y) = (d.x.signum() as i32, d.y.signum() as i32);
+		// how far along the ray (0..1) each grid line crossing happens
+		let first = |a : f32, d : f32, cell : i32| {
+			if d > 0. { (cell as f32 + 1. - a)/d }
+			else if d < 0. { (a - cell as f32)/-d }
+			else { f32::INFINITY }
+		};
+		let (mut t_x, mut t_y) = (first(a.x, d.x, x), first(a.y, d.y, y));
+		let (dt_x, dt_y) = ((1./d.x).abs(), (1./d.y).abs());
+		let mut t = 0.;
+		loop {
+			if self.is_solid(x, y) {
+				return Some(*from + (to - *from)*t);
+			}
+			if (x, y) == end { return None }
+			if t_x < t_y {
+				t = t_x;
+				t_x += dt_x;
+				x += step_x;
+			} else {
+				t = t_y;
+				t_y += dt_y;
+				y += step_y;
+			}
+			if t > 1. { return None }
+		}
+	}
+	pub fn segment_clear(&self, a : &Vec2, b : &Vec2) -> bool {
+		self.raycast(a, &(*b - *a), a.distance(*b)).is_none()
+	}
+	/// Whether a circle touches any solid cell.
+	pub fn circle_overlaps(&self, centre : &Vec2, rad : f32) -> bool {
+		let (min, max) = (
+			self.cell_of(&(*centre - Vec2::splat(rad))),
+			self.cell_of(&(*centre + Vec2::splat(rad))));
+		for y in min.1..=max.1 {
+			for x in min.0..=max.0 {
+				if !self.is_solid(x, y) { continue }
+				let tl = self.tl + vec2(x as f32, y as f32)*self.cell_size;
+				let closest = centre.clamp(tl, tl + self.cell_size);
+				if closest.distance_squared(*centre) < rad*rad {
+					return true;
+				}
+			}
+		}
+		false
+	}
+	/// Push that moves a circle out of the wall cell it overlaps the most.
+	/// Zero if it's clear, or if its centre is already inside a wall.
+	fn circle_push(&self, centre : &Vec2, rad : f32) -> Vec2 {
+		let (min, max) = (
+			self.cell_of(&(*centre - Vec2::splat(rad))),
+			self.cell_of(&(*centre + Vec2::splat(rad))));
+		let mut push = Vec2::ZERO;
+		for y in min.1..=max.1 {
+			for x in min.0..=max.0 {
+				if !self.is_solid(x, y) { continue }
+				let tl = self.tl + vec2(x as f32, y as f32)*self.cell_size;
+				let out = *centre - centre.clamp(tl, tl + self.cell_size);
+				let d = out.length();
+				if d > 0. && d < rad && rad - d > push.length() {
+					push = out/d*(rad - d);
+				}
+			}
+		}
+		push
+	}
+	/// Where a circle at `pos` ends up after trying to move by `delta`. Moves in
+	/// steps of at most half the radius so it can't tunnel, and after each step
+	/// pushes out of any walls, which turns head-on movement into sliding.
+	pub fn slide_circle(&self, pos : &Vec2, delta : Vec2, rad : f32) -> Vec2 {
+		const RESOLVE_ITERATIONS : usize = 4;
+		let steps = (delta.length()/(rad*0.5)).ceil().max(1.);
+		let step = delta/steps;
+		let mut p = *pos;
+		for _ in 0..steps as usize {
+			let next = p + step;
+			let mut resolved = next;
+			for _ in 0..RESOLVE_ITERATIONS {
+				let push = self.circle_push(&resolved, rad);
+				if push == Vec2::ZERO { break }
+				resolved += push;
+			}
+			if self.is_collision(&resolved) { break }
+			p = resolved;
+		}
+		p
+	}
+	/// Centre of the free cell closest to `pos`, searching outwards ring by ring.
+	pub fn nearest_free_cell(&self, pos : &Vec2) -> Option<Vec2> {
+		let c = self.cell_of(pos);
+		let max_ring = self.w.max(self.h) + c.0.abs().max(c.1.abs());
+		let mut best : Option<(f32, Vec2)> = None;
+		for ring in 0..=max_ring {
+			for y in (c.1-ring)..=(c.1+ring) {
+				for x in (c.0-ring)..=(c.0+ring) {
+					if (x - c.0).abs() != ring && (y - c.1).abs() != ring { continue }
+					if self.is_solid(x, y) { continue }
+					let p = self.cell_centre(x, y);
+					let d = p.distance(*pos);
+					if best.is_none_or(|(bd, _)| d < bd) {
+						best = Some((d, p));
+					}
+				}
+			}
+			// cells past this ring are at least `ring` cells away
+			if let Some((d, _)) = best {
+				if d <= ring as f32 * self.cell_size.min_element() {
+					break;
+				}
+			}
+		}
+		best.map(|(_, p)| p)
+	}
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+	use super::*;
+
+	/// One unit per cell from the origin, `#` for wall.
+	pub(crate) fn from_rows(rows : &[&str]) -> CollisionMap {
+		let (w, h) = (rows[0].len() as i32, rows.len() as i32);
+		let mut map = CollisionMap {
+			tl: Vec2::ZERO,
+			cell_size: Vec2::ONE,
+			w,
+			h,
+			bits: vec![0; ((w*h) as usize).div_ceil(64)],
+		};
+		for (y, row) in rows.iter().enumerate() {
+			for (x, c) in row.chars().enumerate() {
+				if c == '#' {
+					let i = y*w as usize + x;
+					map.bits[i/64] |= 1 << (i%64);
+				}
+			}
+		}
+		map
+	}
+	fn block() -> CollisionMap {
+		from_rows(&[
+			"........",
+			"........",
+			"...##...",
+			"...##...",
+			"........",
+			"........",
+		])
+	}
+	fn near(a : Vec2, b : Vec2) -> bool { a.distance(b) < 1e-3 }
+
+	#[test]
+	fn raycast_hits_and_misses() {
+		let map = block();
+		let hit = map.raycast(&vec2(0.5, 2.5), &Vec2::X, 6.).unwrap();
+		assert!(near(hit, vec2(3., 2.5)), "{hit}");
+		assert_eq!(map.raycast(&vec2(0.5, 2.5), &Vec2::X, 2.), None);
+		assert_eq!(map.raycast(&vec2(0.5, 0.5), &Vec2::X, 7.), None);
+		// off the grid counts as wall
+		let hit = map.raycast(&vec2(0.5, 0.5), &-Vec2::Y, 3.).unwrap();
+		assert!(near(hit, vec2(0.5, 0.)), "{hit}");
+		assert!(!map.segment_clear(&vec2(0.5, 0.5), &vec2(7.5, 5.5)));
+		assert!(map.segment_clear(&vec2(0.5, 4.5), &vec2(7.5, 4.5)));
+	}
+	#[test]
+	fn raycast_grazing_corner() {
+		let map = block();
+		let dir = vec2(1., -1.);
+		// passing just above the block's top left corner at (3, 2)
+		assert_eq!(map.raycast(&vec2(0.5, 4.45), &dir, 4.9), None);
+		assert!(map.segment_clear(&vec2(0.5, 4.45), &vec2(3.9, 1.05)));
+		// clipping it
+		let hit = map.raycast(&vec2(0.5, 4.55), &dir, 4.9).unwrap();
+		assert!(near(hit, vec2(3., 2.05)), "{hit}");
+	}
+	#[test]
+	fn starting_inside_a_wall() {
+		let map = block();
+		let inside = vec2(3.6, 2.5);
+		assert_eq!(map.raycast(&inside, &Vec2::X, 5.), Some(inside));
+		assert!(!map.segment_clear(&inside, &vec2(7.5, 2.5)));
+		// stuck, rather than tunnelling further in
+		assert_eq!(map.slide_circle(&inside, vec2(1., 0.), 0.4), inside);
+		let free = map.nearest_free_cell(&inside).unwrap();
+		assert!(near(free, vec2(3.5, 1.5)), "{free}");
+	}
+	#[test]
+	fn slide_circle_stops_and_slides() {
+		let map = block();
+		let rad = 0.4;
+		let p = map.slide_circle(&vec2(1.5, 2.5), vec2(3., 0.), rad);
+		assert!(p.x <= 3. - rad + 1e-3 && (p.y - 2.5).abs() < 1e-3, "{p}");
+		assert!(!map.circle_overlaps(&p, rad*0.99));
+		// pressed into the top of the block, it still gets along it
+		let p = map.slide_circle(&vec2(2.5, 1.5), vec2(2., 0.5), rad);
+		assert!(p.x > 4. && p.y <= 2. - rad + 1e-3, "{p}");
+		assert!(!map.circle_overlaps(&p, rad*0.99));
+	}
+	#[test]
+	fn nearest_free_cell() {
+		let map = block();
+		assert_eq!(map.nearest_free_cell(&vec2(0.2, 0.7)), Some(vec2(0.5, 0.5)));
+		// off the grid, back to the closest edge
+		assert_eq!(map.nearest_free_cell(&vec2(-3.2, 1.5)), Some(vec2(0.5, 1.5)));
+		assert_eq!(from_rows(&["##", "##"]).nearest_free_cell(&vec2(0.5, 0.5)), None);
+	}
+}
@@ -8,6 +8,16 @@ pub const H : f32 = REAL_H as f32 * SCALE;
 
 pub const DEFAULT_FONT_SIZE : f32 = 8.;
 
+/// Length of one simulation step, independent of the frame rate.
+pub const FIXED_DT : f32 = 1./60.;
+
+pub const SAVE_PATH : &str = "hive.sav";
+pub const KEY_SAVE : KeyCode = KeyCode::F5;
+pub const KEY_LOAD : KeyCode = KeyCode::F9;
+pub const KEY_PAUSE : KeyCode = KeyCode::P;
+pub const KEY_SLOWER : KeyCode = KeyCode::Minus;
+pub const KEY_FASTER : KeyCode = KeyCode::Equal;
+
 pub fn window_conf() -> Conf {
 	Conf {
 		window_width: REAL_W,
@@ -18,3 +28,15 @@ pub fn window_conf() -> Conf {
 		..Conf::default()
 	}
 }
+
+pub fn arg_value(name : &str) -> Option<String> {
+	std::env::args()
+		.skip_while(|a| a != name)
+		.nth(1)
+}
+/// `--seed <n>` on the command line, or the current time otherwise.
+pub fn seed_from_args() -> u64 {
+	arg_value("--seed")
+		.and_then(|s| s.parse().ok())
+		.unwrap_or_else(|| (miniquad::date::now()*1000.) as u64)
+}
@@ -1,31 +1,69 @@
 use macroquad::prelude::*;
-use std::collections::HashMap;
+use std::{collections::HashMap, io};
 
-use crate::{game_objects::RenderData, prelude::{COL_MARKER_FOOD, random_angle}, gobj::PLAYER_RAD};
+use crate::{game_objects::RenderData, prelude::{COL_MARKER_FOOD, random_angle, Rng, ByteWriter, ByteReader, SpatialGrid, CellKey, invalid_data}, gobj::PLAYER_RAD};
 
+pub const FOOD_SOURCE_MIN : f32 = 30.;
+pub const FOOD_SOURCE_MAX : f32 = 100.;
+/// How many sugar textures there are to pick from.
+const SUGAR_KINDS : usize = 4;
+
+/// A food source, or a bite of one being carried.
 #[derive(Debug, PartialEq, Clone)]
 pub struct Food {
 	pub pos : Vec2,
+	/// How big it is with `value` at `full`, it shrinks as it's eaten.
 	pub size : f32,
 	pub value : f32,
+	pub full : f32,
 	pub angle : f32,
 	pub index : usize,
 }
 impl Food {
-	pub fn new(pos : &Vec2) -> Self {
-		let value = rand::gen_range(1., 5.);
-		let size = value*PLAYER_RAD;
+	pub fn new(pos : &Vec2, amount : f32, rng : &mut Rng) -> Self {
 		Food {
 			pos: *pos,
-			size,
-			value,
-			angle: random_angle(),
-			index: rand::gen_range(0, 4),
+			size: amount.sqrt()*PLAYER_RAD*0.8,
+			value: amount,
+			full: amount,
+			angle: random_angle(rng),
+			index: rng.gen_range(0, SUGAR_KINDS),
+		}
+	}
+	pub fn radius(&self) -> f32 {
+		self.size*(self.value/self.full).sqrt()/2.
+	}
+	/// Breaks off up to `amount`, drawn to the same scale as what's left.
+	pub fn bite(&mut self, amount : f32) -> Food {
+		let taken = amount.min(self.value);
+		self.value -= taken;
+		Food { value: taken, ..self.clone() }
+	}
+	pub fn write(&self, w : &mut ByteWriter) {
+		w.vec2(&self.pos);
+		w.f32(self.size);
+		w.f32(self.value);
+		w.f32(self.full);
+		w.f32(self.angle);
+		w.usize(self.index);
+	}
+	pub fn read(r : &mut ByteReader) -> io::Result<Self> {
+		let f = Food {
+			pos: r.vec2()?,
+			size: r.f32()?,
+			value: r.f32()?,
+			full: r.f32()?,
+			angle: r.f32()?,
+			index: r.usize()?,
+		};
+		if f.index >= SUGAR_KINDS {
+			return Err(invalid_data(&format!("bad sugar index {}", f.index)));
 		}
+		Ok(f)
 	}
 	pub fn render(&self, rd : &RenderData) {
 		let pos = rd.cast_pos(&self.pos);
-		let dim = Vec2::ONE*rd.scale_unit(self.size);
+		let dim = Vec2::ONE*rd.scale_unit(self.radius()*2.);
 		draw_texture_ex(rd.assets.clone().unwrap().tex_sugars[self.index],
 			pos.x - dim.x/2., pos.y - dim.y/2.,
 			COL_MARKER_FOOD,
@@ -37,87 +75,100 @@ impl Food {
 	}
 }
 
+/// Stable handle to a piece of food; ids are never reused, so a stale one
+/// just stops resolving once the food is taken.
 pub type FoodID = usize;
+pub const FOOD_SEARCH_RANGE : f32 = 42.;
 #[derive(Debug)]
 pub struct FoodWorld {
-	grid_size : f32,
-	food : HashMap<(i32, i32), HashMap<FoodID, Food>>,
+	food : SpatialGrid<(FoodID, Food)>,
+	// which cell each piece of food lives in, food never moves while in the world
+	index : HashMap<FoodID, CellKey>,
 	next_id : FoodID,
 }
 impl FoodWorld {
 	pub fn new(grid_size : f32) -> Self {
 		FoodWorld {
-			grid_size,
-			food: HashMap::new(),
+			food: SpatialGrid::new(grid_size),
+			index: HashMap::new(),
 			next_id: 0,
 		}
 	}
-	pub fn pos_to_key(&self, pos : &Vec2) -> (i32, i32) {
-		let key = *pos / self.grid_size;
-		(key.x.floor() as i32, key.y.floor() as i32)
-	}
-	pub fn put_food(&mut self, f : Food) {
-		let key = self.pos_to_key(&f.pos);
-		if !self.food.contains_key(&key) {
-			self.food.insert(key, HashMap::new());
-		}
+	pub fn put_food(&mut self, f : Food) -> FoodID {
 		self.next_id += 1;
-		self.food
-			.get_mut(&key)
-			.unwrap()
-			.insert(self.next_id, f);
+		self.insert(self.next_id, f);
+		self.next_id
 	}
+	fn insert(&mut self, id : FoodID, f : Food) {
+		self.index.insert(id, self.food.key(&f.pos));
+		self.food.insert(f.pos, (id, f));
+	}
+	/// The closest food within `FOOD_SEARCH_RANGE` of `pos+heading`.
 	pub fn find_food(&self, pos : &Vec2, heading : &Vec2) -> Option<FoodID> {
-		let p = *pos+*heading;
-		let k = self.pos_to_key(&p);
-
-		let (mut winner, mut md) = (None, f32::MAX);
-		for x in (k.0-1)..=(k.0+1) {
-			for y in (k.1-1)..=(k.1+1) {
-				if !self.food.contains_key(&(x, y)) {
-					continue;
-				}
-				for m in &self.food[&(x, y)] {
-					let d = m.1.pos.distance(p);
-					if d < md {
-						winner = Some(*m.0);
-						md = d;
-					}
-				}
-			}
-		}
-		winner
+		self.food
+			.k_nearest(&(*pos+*heading), 1, FOOD_SEARCH_RANGE)
+			.first()
+			.map(|(_, (id, _))| *id)
 	}
 	pub fn get_food(&self, food_id : FoodID) -> Option<&Food> {
-		for (_, v) in self.food.iter() {
-			if v.contains_key(&food_id) {
-				return v.get(&food_id)
-			}
+		self.food
+			.cell(self.index.get(&food_id)?)
+			.iter()
+			.find(|(_, (id, _))| *id == food_id)
+			.map(|(_, (_, f))| f)
+	}
+	/// Takes up to `capacity` off a piece of food, removing it once it's empty.
+	pub fn take_bite(&mut self, food_id : FoodID, capacity : f32) -> Option<Food> {
+		let key = *self.index.get(&food_id)?;
+		let (_, (_, f)) = self.food
+			.cell_mut(&key)
+			.iter_mut()
+			.find(|(_, (id, _))| *id == food_id)?;
+		if f.value <= capacity {
+			return self.take_food(food_id);
 		}
-		None
+		Some(f.bite(capacity))
 	}
 	pub fn take_food(&mut self, food_id : FoodID) -> Option<Food> {
-		for (_, v) in self.food.iter_mut() {
-			if v.contains_key(&food_id) {
-				return v.remove(&food_id)
-			}
-		}
-		None
+		let key = self.index.remove(&food_id)?;
+		let pos = self.food.cell_pos(&key);
+		self.food
+			.remove(&pos, |(id, _)| *id == food_id)
+			.map(|(_, (_, f))| f)
 	}
 
-	pub fn update(&mut self, _d : f32) {
-		self.food.retain(|_, v| v.len() != 0);
+	pub fn write_state(&self, w : &mut ByteWriter) {
+		// ids only ever grow, so each cell is already in id order
+		w.seq(&self.food.cells(), |w, (k, v)| {
+			w.i32(k.0);
+			w.i32(k.1);
+			w.seq(v, |w, (_, (id, f))| {
+				w.usize(*id);
+				f.write(w);
+			});
+		});
+		w.usize(self.next_id);
 	}
+	pub fn read_state(&mut self, r : &mut ByteReader) -> io::Result<()> {
+		let cells = r.seq(|r| {
+			r.i32()?;
+			r.i32()?;
+			r.seq(|r| Ok((r.usize()?, Food::read(r)?)))
+		})?;
+		let next_id = r.usize()?;
+		self.food.clear();
+		self.index.clear();
+		for (id, f) in cells.into_iter().flatten() {
+			self.insert(id, f);
+		}
+		self.next_id = next_id;
+		Ok(())
+	}
+
 	pub fn render(&self, rd : &RenderData) {
-		for (_k, v) in &self.food {
-			//let pos = vec2(
-			//	k.0 as f32 * self.grid_size,
-			//	k.1 as f32 * self.grid_size
-			//);
-			//let pos = pos - rd.camera_offset();
-			//draw_rectangle_lines(pos.x, pos.y, self.grid_size, self.grid_size, 2., PINK);
-			for f in v {
-				f.1.render(rd);
+		for (_, v) in self.food.cells() {
+			for (_, (_, f)) in v {
+				f.render(rd);
 			}
 		}
 	}
@@ -1,13 +1,15 @@
-use std::collections::HashMap;
+use std::{collections::BTreeMap, io};
 
 use macroquad::prelude::{Vec2, vec2, is_key_down, KeyCode};
-use crate::{config::{W, H}, prelude::{REAL_H, REAL_W, Assets}};
+use crate::{config::{W, H}, prelude::{REAL_H, REAL_W, Assets, FrameInput, ByteWriter, ByteReader, lerp}};
 
 pub struct RenderData {
 	pub camera_pos : Vec2,
 	pub zoom : f32,
 	pub assets : Option<Assets>,
 	pub d : f32,
+	/// How far into the next fixed step this frame is, in `[0, 1)`.
+	pub alpha : f32,
 }
 impl RenderData {
 	pub fn new() -> Self {
@@ -16,6 +18,7 @@ impl RenderData {
 			zoom: 1.0,
 			assets: None,
 			d: 0.1,
+			alpha: 1.0,
 		}
 	}
 	pub fn init(&mut self, a : &Assets) {
@@ -30,17 +33,20 @@ impl RenderData {
 	pub fn scale_unit(&self, s : f32) -> f32 {
 		s*self.zoom
 	}
+	pub fn interp(&self, prev : &Vec2, cur : &Vec2) -> Vec2 {
+		lerp(*prev, *cur, self.alpha)
+	}
 }
 
 pub trait GameObject {
 	fn init(&mut self) {}
-	fn update(&mut self) -> bool { true }
+	fn update(&mut self, _d : f32, _input : &FrameInput) -> bool { true }
 	fn render(&self, rd : &RenderData);
 }
 pub type GameObjectID = usize;
 
 pub struct ObjectSet<T : GameObject> {
-	pub objects : HashMap<GameObjectID, T>,
+	pub objects : BTreeMap<GameObjectID, T>,
 	pub to_be_destroyed : Vec<GameObjectID>,
 	next_id : GameObjectID,
 }
@@ -49,14 +55,14 @@ pub struct ObjectSet<T : GameObject> {
 impl<T : GameObject> ObjectSet<T> {
 	pub fn new() -> Self {
 		ObjectSet {
-			objects: HashMap::new(),
+			objects: BTreeMap::new(),
 			to_be_destroyed: Vec::new(),
 			next_id: 0,
 		}
 	}
-	pub fn update(&mut self) {
+	pub fn update(&mut self, d : f32, input : &FrameInput) {
 		for (id, obj) in self.objects.iter_mut() {
-			if !obj.update() {
+			if !obj.update(d, input) {
 				self.to_be_destroyed.push(*id);
 			}
 		}
@@ -80,6 +86,24 @@ impl<T : GameObject> ObjectSet<T> {
 			.expect(&format!("No GameObject with id {id}"))
 	}
 
+	pub fn write_state(&self, w : &mut ByteWriter, f : impl Fn(&mut ByteWriter, &T)) {
+		w.usize(self.next_id);
+		w.usize(self.objects.len());
+		for (id, o) in self.objects.iter() {
+			w.usize(*id);
+			f(w, o);
+		}
+	}
+	/// Replaces every object, keeping the saved ids. `init` is not called again.
+	pub fn read_state(&mut self, r : &mut ByteReader, mut f : impl FnMut(&mut ByteReader) -> io::Result<T>) -> io::Result<()> {
+		let next_id = r.usize()?;
+		let objects = r.seq(|r| Ok((r.usize()?, f(r)?)))?;
+		self.objects = objects.into_iter().collect();
+		self.to_be_destroyed.clear();
+		self.next_id = next_id;
+		Ok(())
+	}
+
 	pub fn schedule_destroy(&mut self, id : GameObjectID) {
 		self.to_be_destroyed.push(id);
 	}
@@ -1,111 +1,54 @@
 use std::cell::RefCell;
+use std::io;
 use std::rc::Rc;
 
 use macroquad::prelude::*;
-use crate::food::Food;
 use crate::hive::Hive;
-use crate::markers::Marker;
 use crate::prelude::*;
 use crate::gobj::*;
+use crate::simulation::{Simulation, MAX_DEATHS};
 use crate::world::MAP_DIMS;
 use crate::world::MAP_TOPLEFT;
-use crate::world::World;
+use crate::world::TrailBackend;
 
 pub const PREVIEW_LENGTH : f32 = 5.0;
-pub const FOOD_TIMER_LOW : f32 = 15.0;
-pub const FOOD_TIMER_HIGH : f32 = 36.0;
+/// How long the surface keeps going after the player dies, before the nest.
+pub const DYING_LENGTH : f32 = 2.0;
 pub enum GameState {
 	On,
 	Preview(f32),
+	Dying(f32),
 	Over,
 }
 pub struct Gameplay {
-	pub objs : ObjectSet<Gobj>,
-	pub player_id : GameObjectID,
+	pub sim : Simulation,
 	pub rd : RenderData,
-	pub world : Rc<RefCell<World>>,
-	pub spawn_queue : Rc<RefCell<Vec<Gobj>>>,
 	pub state : GameState,
 	load_timer : f32,
-	food_timer: f32,
+	/// Shown once the run's over, so it can be played again with `--seed`.
+	seed : u64,
 }
 impl Gameplay {
-	pub fn new(hive : Rc<RefCell<Hive>>) -> Self {
+	pub fn new(hive : Rc<RefCell<Hive>>, seed : u64, trails : TrailBackend) -> Self {
 		Gameplay {
-			objs: ObjectSet::new(),
-			player_id: 0,
+			sim: Simulation::new(hive, seed, trails),
 			rd: RenderData::new(),
-			world : Rc::new(RefCell::new(World::new(hive))),
-			spawn_queue: Rc::new(RefCell::new(Vec::new())),
 			state: GameState::On,
 			load_timer: 1.,
-			food_timer: 0.,
+			seed,
 		}
 	}
 
 	pub fn presim(&mut self) {
-		let mut q = SignalQueue::new();
-
-		for _ in 0..100 {
-			self.objs.create(Gobj::new_ant(
-					self.spawn_queue.clone(),
-					self.world.clone(),
-					&random_ring_point(
-						&HOME_POS,
-						ANT_RAD,
-						ANT_RAD*3.
-					)
-				));
-		}
-		for _ in 0..200 {
-			self.update(&mut q);
-		}
-		match self.state {
-			GameState::Over => self.restart(),
-			_ => ()
-		}
-	}
-	pub fn spawn_food(&mut self) {
-		for _ in 0..3 {
-			let mut place;
-			loop {
-				place = random_ring_point(&HOME_POS, MAP_DIMS.x*0.1, MAP_DIMS.x/2.);
-				if !self.world.borrow().is_collision(&place)
-					&& place.distance(self.player_pos()) > W {
-						break;
-				}
-			}
-			for _ in 0..rand::gen_range(5, 25) {
-				self.world.borrow_mut().food.put_food(Food::new(
-						&random_ring_point(
-							&place,
-							ANT_RAD,
-							ANT_RAD*6.
-							)
-						)
-					);
-			}
+		self.sim.presim();
+		if self.sim.lost() {
+			self.restart();
 		}
 	}
 	pub fn lose(&mut self) {
 		self.state = GameState::Over;
 	}
-	pub fn restart(&mut self) {
-		todo!();
-		self.state = GameState::On;
-	}
-	pub fn player_pos(&self) -> Vec2 {
-		match self.objs.get_obj(self.player_id) {
-			Gobj::Player(_, _, pos, _, _, _) => *pos,
-			_ => panic!("player_id ({}) not pointing to a Player!", self.player_id)
-		}
-	}
-	fn set_player_pos(&mut self, p : &Vec2) {
-		match self.objs.get_obj_mut(self.player_id) {
-			Gobj::Player(_, _, pos, _, _, _) => *pos = *p,
-			_ => panic!("player_id ({}) not pointing to a Player!", self.player_id)
-		}
-	}
+	pub fn player_pos(&self) -> Vec2 { self.sim.player_pos() }
 	fn render_bg_tex(&self) {
 		const TS : f32 = 128.;
 		const TL : u8 = 8;
@@ -157,45 +100,67 @@ impl Gameplay {
 }
 impl Scene for Gameplay {
 	fn init(&mut self, a : &Assets) {
-		self.player_id = self.objs.create(
-			Gobj::new_player(
-				self.spawn_queue.clone(),
-				self.world.clone(),
-				&Vec2::ZERO
-				)
-			);
 		self.rd.init(a);
-		self.world.borrow_mut().init(&a.tex_map, &MAP_DIMS);
+		self.sim.init(&a.tex_map.get_texture_data());
 
 		self.presim();
 	}
+	fn write_state(&self, w : &mut ByteWriter) {
+		match self.state {
+			GameState::On => w.u8(0),
+			GameState::Preview(left) => { w.u8(1); w.f32(left); },
+			GameState::Over => w.u8(2),
+			GameState::Dying(left) => { w.u8(3); w.f32(left); },
+		}
+		w.f32(self.load_timer);
+		w.vec2(&self.rd.camera_pos);
+		w.f32(self.rd.zoom);
+		self.sim.write_state(w);
+	}
+	fn read_state(&mut self, r : &mut ByteReader) -> io::Result<()> {
+		self.state = match r.u8()? {
+			0 => GameState::On,
+			1 => GameState::Preview(r.f32()?),
+			2 => GameState::Over,
+			3 => GameState::Dying(r.f32()?),
+			t => return Err(invalid_data(&format!("bad GameState tag {t}"))),
+		};
+		self.load_timer = r.f32()?;
+		self.rd.camera_pos = r.vec2()?;
+		self.rd.zoom = r.f32()?;
+		self.sim.read_state(r)
+	}
+	fn restart(&mut self) {
+		self.sim.reset();
+		self.state = GameState::On;
+		self.load_timer = 1.;
+		self.rd.camera_pos = self.player_pos();
+		self.rd.zoom = 1.0;
+		self.presim();
+		self.sim.objs.create(Gobj::Fader(1.0));
+	}
 	fn load(&mut self) {
 		self.load_timer = 1.;
-		self.objs.create(Gobj::Fader(1.0));
+		// out of the nest entrance, back on their feet if they'd died
+		self.sim.respawn_player(&(HOME_POS+vec2(0.0, ANT_HOME_DEPOSIT_RANGE)));
+		self.sim.objs.create(Gobj::Fader(1.0));
 	}
-    fn update(&mut self, q : &mut SignalQueue) {
+    fn update(&mut self, q : &mut SignalQueue, d : f32, input : &FrameInput) {
 		use GameState::*;
-		let d = get_frame_time().min(0.2).max(0.001);
-		self.rd.d = d;
 		match self.state {
 			On => {
-				self.world.borrow_mut().hive.borrow_mut().update(d);
-				self.objs.update();
-				self.world.borrow_mut().marker.update(d);
-				self.world.borrow_mut().food.update(d);
+				self.sim.step(d, input);
 
-				if self.world.borrow().hive.borrow().lost() {
-					self.lose();
+				if self.sim.player_dead() {
+					self.sim.kill_player();
+					self.state = Dying(DYING_LENGTH);
+					return;
 				}
-
-				if self.food_timer >= 0. {
-					self.food_timer -= d;
-				} else if self.world.borrow().hive.borrow().state_as_float() < 0.9 {
-					self.spawn_food();
-					self.food_timer = rand::gen_range(FOOD_TIMER_LOW, FOOD_TIMER_HIGH);
+				if self.sim.lost() {
+					self.lose();
 				}
 
-				if self.world.borrow().hive.borrow_mut().did_player_give() {
+				if self.sim.hive.borrow_mut().did_player_give() {
 					self.state = Preview(PREVIEW_LENGTH);
 				}
 				if self.load_timer >= 0.0 {
@@ -204,62 +169,68 @@ impl Scene for Gameplay {
 				if self.player_pos().distance(HOME_POS) < PLAYER_PICKUP_RANGE
 					&& self.load_timer <= 0.0 {
 					q.send(Signal::SetScene(0));
-					self.set_player_pos(&(HOME_POS+vec2(0.0, ANT_HOME_DEPOSIT_RANGE)));
-				}
-
-				for obj in self.spawn_queue.borrow().iter() {
-					self.objs.create(obj.clone());
 				}
-				self.spawn_queue.borrow_mut().clear();
-				if !self.world.borrow().marker
-					.local_markers(&HOME_POS, &Vec2::ZERO, &|_| true)
-						.iter()
-						.any(|m| *m.pos() == HOME_POS) {
-					self.world.borrow_mut().marker.create_marker(Marker::Home(HOME_POS, HOME_MARKER_LIFE), self.spawn_queue.clone());
+			},
+			// the colony carries on while the burst plays out, then it's back to the nest
+			Dying(left) => {
+				self.sim.step(d, &FrameInput::default());
+				if left > d {
+					self.state = Dying(left - d);
+				} else if self.sim.lost() || self.sim.deaths >= MAX_DEATHS {
+					self.lose();
+				} else {
+					self.state = On;
+					self.sim.hive.borrow_mut().player_died();
+					q.send(Signal::SetScene(0));
 				}
-
-				self.rd.camera_pos = lerp(
-					self.rd.camera_pos,
-					self.player_pos() + get_ivn()*10.,
-					d*6.);
-				self.rd.zoom = 1.0;
 			},
 			Preview(_) => {
-				self.rd.camera_pos = lerp(
-					self.rd.camera_pos,
-					self.player_pos(),
-					d*6.);
 				if let Preview(ref mut left) = self.state {
 					*left -= d;
 					if *left < 0.0 { self.state = On; }
 				}
 			},
 			Over => {
-				if is_key_pressed(KeyCode::R) {
-					self.restart();
+				if input.restart {
+					q.send(Signal::Restart);
 				}
 			}
 		}
     }
 
-    fn render(&mut self, _q : &mut SignalQueue) {
+    fn render(&mut self, _q : &mut SignalQueue, alpha : f32) {
 		use GameState::*;
+		let d = get_frame_time().clamp(0.001, 0.2);
+		self.rd.d = d;
+		self.rd.alpha = alpha;
+		let player_pos = self.sim.player_render_pos(&self.rd);
 		match self.state {
-			On => {
+			On | Dying(_) => {
+				self.rd.camera_pos = lerp(
+					self.rd.camera_pos,
+					player_pos + self.sim.player_facing()*10.,
+					d*6.);
+				self.rd.zoom = 1.0;
+
 				self.render_bg_tex();
 				self.render_map_tex();
-				self.world.borrow().food.render(&self.rd);
-				self.objs.render(&self.rd);
+				self.sim.world.borrow().food.render(&self.rd);
+				self.sim.objs.render(&self.rd);
 				self.render_map_vignette(&MAP_TOPLEFT, &MAP_DIMS, 1.8);
 			},
-			Preview(left) => {
+			Preview(_left) => {
+				self.rd.camera_pos = lerp(
+					self.rd.camera_pos,
+					player_pos,
+					d*6.);
+
 				clear_background(COL_BG);
 				self.render_map_tex();
 				//let a = 1.0 - left/PREVIEW_LENGTH;
 				//draw_rectangle(0.,0.,W,H,Color{r: COL_BG.r, g: COL_BG.g, b: COL_BG.b, a});
 
-				self.objs.render(&self.rd);
-				self.world.borrow().marker.render(&self.rd);
+				self.sim.objs.render(&self.rd);
+				self.sim.world.borrow().render_trails(&self.rd);
 				self.render_map_vignette(&MAP_TOPLEFT, &MAP_DIMS, 1.8);
 
 				//self.debug_render();
@@ -268,9 +239,9 @@ impl Scene for Gameplay {
 			Over => {
 				clear_background(BLACK);
 				self.render_map_tex();
-				self.world.borrow().marker.render(&self.rd);
-				self.world.borrow().food.render(&self.rd);
-				self.objs.render(&self.rd);
+				self.sim.world.borrow().render_trails(&self.rd);
+				self.sim.world.borrow().food.render(&self.rd);
+				self.sim.objs.render(&self.rd);
 				self.render_map_vignette(&MAP_TOPLEFT, &MAP_DIMS, 1.8);
 
 				const VS : f32 = 2.5;
@@ -278,9 +249,13 @@ impl Scene for Gameplay {
 					DrawTextureParams{ dest_size: Some(vec2(W, W)*VS), ..DrawTextureParams::default() });
 
 				self.rd.zoom = lerp(self.rd.zoom, 0.0, self.rd.d*0.4);
+				quick_text(&format!("seed {}", self.seed), vec2(2., 8.), WHITE);
+				if let Some(avg) = self.sim.world.borrow().trips.average() {
+					quick_text(&format!("average trip length: {avg:.0}"), vec2(2., 16.), WHITE);
+				}
 			}
 		}
-		let f = self.objs.objects.iter().find(|(_, o)| match o { Gobj::Fader(_) => true, _ => false });
+		let f = self.sim.objs.objects.iter().find(|(_, o)| match o { Gobj::Fader(_) => true, _ => false });
 		if f.is_some() {
 			f.unwrap().1.render(&self.rd);
 		}
@@ -296,45 +271,60 @@ impl Gameplay {
 		}
 		let mp = mouse_pos_scaled_rd(&self.rd);
 		if is_mouse_button_pressed(MouseButton::Left) {
-			self.objs.create(Gobj::new_particles(
+			let p = Gobj::new_particles(
 					&mp,
 					100,
 					3.,
 					1.,
 					GREEN,
 					ParticleStyle::Explosive(100.,
-					0.07))
-				);
+					0.07),
+					&mut self.sim.world.borrow_mut().rng);
+			self.sim.objs.create(p);
 		}
 		else if is_mouse_button_pressed(MouseButton::Right) {
-			self.objs.create(Gobj::new_particles(
+			let p = Gobj::new_particles(
 					&mp,
 					100,
 					3.,
 					10.,
 					ORANGE,
-					ParticleStyle::Orbit(4., 0.03))
-				);
+					ParticleStyle::Orbit(4., 0.03),
+					&mut self.sim.world.borrow_mut().rng);
+			self.sim.objs.create(p);
 		}
 		if is_key_pressed(KeyCode::Key1) {
 			for _ in 0..20 {
-				self.objs.create(Gobj::new_ant(
-						self.spawn_queue.clone(),
-						self.world.clone(),
-						&random_ring_point(
-							&mouse_pos_scaled_rd(&self.rd),
-							ANT_RAD,
-							ANT_RAD*3.
-						)
+				let pos = random_ring_point(
+					&mp,
+					ANT_RAD,
+					ANT_RAD*3.,
+					&mut self.sim.world.borrow_mut().rng
+				);
+				self.sim.objs.create(Gobj::new_ant(
+						self.sim.spawn_queue.clone(),
+						self.sim.world.clone(),
+						&pos
 					));
 			}
 		}
 		if is_key_pressed(KeyCode::Key2) {
-			self.spawn_food();
+			self.sim.spawn_food();
+		}
+		if is_key_pressed(KeyCode::Key3) {
+			self.sim.spawn_predator();
 		}
 	}
 	fn debug_render(&mut self) {
 		if is_key_down(KeyCode::C) { self.render_debug_map_col() }
+		if is_key_down(KeyCode::H) {
+			let world = self.sim.world.borrow();
+			world.home_flow.render(&world.collision, &self.rd);
+			for p in world.find_path(&self.player_pos(), &HOME_POS).unwrap_or_default() {
+				let p = self.rd.cast_pos(&p);
+				draw_circle(p.x, p.y, 2., YELLOW);
+			}
+		}
 		let hcp = self.rd.cast_pos(&HOME_POS);
 		draw_circle(hcp.x, hcp.y, self.rd.scale_unit(ANT_HOME_DEPOSIT_RANGE), DARKBLUE);
 		let hbp = self.rd.cast_pos(&vec2(-HARD_BOUNDS.x, -HARD_BOUNDS.y));
@@ -345,8 +335,8 @@ impl Gameplay {
 		draw_circle(mp.x, mp.y, 4., PINK);
 	}
 	fn render_debug_map_col(&self) {
-		let css = self.world.borrow().collision_cell_size*self.rd.scale_unit(1.);
-		for (p, b) in self.world.borrow().collision_map.iter() {
+		let css = self.sim.world.borrow().collision.cell_size()*self.rd.scale_unit(1.);
+		for (p, b) in self.sim.world.borrow().collision.cells() {
 			let p = self.rd.cast_pos(
 				&(vec2(p.0 as f32, p.1 as f32)
 				* css
@@ -354,7 +344,7 @@ impl Gameplay {
 			draw_rectangle_lines(p.x, p.y,
 				css.x,
 				css.y, 1.0, RED);
-			if *b {
+			if b {
 				draw_rectangle(p.x, p.y,
 					css.x,
 					css.y, RED);
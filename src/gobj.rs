@@ -1,9 +1,14 @@
 use crate::food::Food;
-use crate::markers::Marker;
+use crate::hive::QUEEN_MAX_FOOD;
+use crate::nest::{QUEEN_POS, NEST_STORE};
+use crate::markers::{Marker, MARKER_DEPOSIT, MARKER_MERGE_RADIUS, ALARM_DEPOSIT};
+use crate::predators::*;
+use crate::pheromones::Pheromone;
 use crate::world::MAP_DIMS;
 use crate::world::World;
 use std::cell::RefCell;
 use std::f32::consts::PI;
+use std::io;
 use std::rc::Rc;
 
 use macroquad::prelude::*;
@@ -13,8 +18,13 @@ use crate::ants::*;
 const PLAYER_SPEED : f32 = ANT_SPEED*3.;
 pub const PLAYER_RAD : f32 = 4.;
 pub const PLAYER_PICKUP_RANGE : f32 = PLAYER_RAD * 2.0;
+pub const PLAYER_MAX_HEALTH : f32 = 3.;
+/// Grace period after getting hurt, so one bite can't land every step.
+const PLAYER_INVULNERABLE_TIME : f32 = 1.5;
+/// Most food the player takes off a source at once.
+const PLAYER_CARRY : f32 = 10.;
 
-const ANT_SPEED : f32 = 15.0;
+pub const ANT_SPEED : f32 = 15.0;
 pub const ANT_RAD : f32 = PLAYER_RAD * 0.8;
 const ANT_TURN_SPEED : f32 = 8.0;
 const ANT_WANDER_TURN_SPEED : f32 = 0.8;
@@ -25,6 +35,44 @@ pub const ANT_HOME_DEPOSIT_RANGE : f32 = ANT_RAD*7.;
 
 pub const HOME_MARKER_LIFE : f32 = 60.0;
 pub const FOOD_MARKER_LIFE : f32 = 35.0;
+pub const ALARM_MARKER_LIFE : f32 = 8.0;
+
+pub const ANT_MAX_ENERGY : f32 = 100.;
+/// Energy an ant burns per second just being alive.
+const ANT_IDLE_DRAIN : f32 = 0.5;
+/// Energy an ant burns per unit walked.
+const ANT_WALK_DRAIN : f32 = 0.05;
+/// Below this an ant drops what it's doing and heads home to eat.
+const ANT_HUNGRY_ENERGY : f32 = 30.;
+/// How close two ants have to be to pass food between them.
+pub const ANT_RELAY_RANGE : f32 = ANT_RAD*3.;
+/// How far an ant carries food itself before it'll hand it over.
+const ANT_RELAY_MIN_CARRY : f32 = ANT_MARKER_DIST*10.;
+/// How long the link to whoever handed an ant its food stays drawn.
+const ANT_HANDOFF_TIME : f32 = 0.6;
+/// How long an ant keeps fleeing or gathering after it last smelled alarm.
+const ANT_ALARM_TIME : f32 = 4.;
+/// Share of the alarm it smells that a gathering ant lays again, so the
+/// warning spreads but dies out a few ants down the line.
+const ANT_ALARM_RELAY : f32 = 0.4;
+/// Weakest alarm worth passing on.
+const ANT_ALARM_RELAY_MIN : f32 = MARKER_DEPOSIT*0.5;
+const CORPSE_LIFE : f32 = 10.;
+const COL_CORPSE : Color = Color::new(0.25, 0.2, 0.2, 1.);
+
+pub const QUEEN_RAD : f32 = PLAYER_RAD*2.5;
+/// Food the queen eats per second.
+const QUEEN_APPETITE : f32 = 1.;
+/// How close the player or a nurse has to get to feed the queen.
+pub const QUEEN_FEED_RANGE : f32 = QUEEN_RAD*3.;
+const COL_QUEEN : Color = Color::new(1., 0.85, 0.6, 1.);
+const NURSE_LOAD : f32 = 5.;
+const NURSE_STORE_RANGE : f32 = ANT_RAD*4.;
+const NURSE_WAYPOINT_RANGE : f32 = ANT_RAD;
+/// How long a nurse waits to look for a way again after finding none.
+const NURSE_REPATH_TIME : f32 = 1.;
+
+const MIN_SLIDE_PROGRESS : f32 = 0.25;
 
 pub const HOME_POS : Vec2 = Vec2::ZERO;
 pub const HARD_BOUNDS : Vec2 = Vec2::new(MAP_DIMS.x/2., MAP_DIMS.y/2.);
@@ -37,67 +85,203 @@ pub enum ParticleStyle {
 #[allow(dead_code)]
 #[derive(Debug, Clone)]
 pub enum Gobj {
-	Player(Rc<RefCell<Vec<Gobj>>>, Rc<RefCell<World>>, Vec2, Marker, Vec2, Option<Food>),
-	Ant(Rc<RefCell<Vec<Gobj>>>, Rc<RefCell<World>>, Vec2, Vec2, f32, Vec2, AntState),
-	Scout(Rc<RefCell<Vec<Gobj>>>, Rc<RefCell<World>>, Vec2, Vec2, f32, Vec2),
+	/// The last two are health and how long it's left invulnerable after a hit.
+	Player(Rc<RefCell<Vec<Gobj>>>, Rc<RefCell<World>>, Vec2, Vec2, Marker, Vec2, Option<Food>, Vec2, f32, f32),
+	/// The last field is where its food was handed over from, and for how much longer that's drawn.
+	Ant(Rc<RefCell<Vec<Gobj>>>, Rc<RefCell<World>>, Vec2, Vec2, Vec2, f32, Vec2, AntState, Caste, f32, Option<(Vec2, f32)>),
+	Corpse(Vec2, Vec2, f32, f32),
+	Queen(Rc<RefCell<World>>, Vec2, f32),
+	/// Carries food from the nest's stores to the queen. The last field is how
+	/// long until it tries pathing again after failing to.
+	Nurse(Rc<RefCell<World>>, Vec2, Vec2, Vec<Vec2>, f32, f32),
+	/// The usize is how many ants it's eaten.
+	Predator(Rc<RefCell<Vec<Gobj>>>, Rc<RefCell<World>>, Vec2, Vec2, PredatorState, PredatorKind, Vec2, usize),
 	Particles(f32, f32, Color, Vec2, ParticleStyle, Vec<Vec2>, Vec<Vec2>, Vec<f32>),
 	Fader(f32),
 }
 impl Gobj {
+	/// An ant of whichever caste the hive rolls.
 	pub fn new_ant(sq : Rc<RefCell<Vec<Gobj>>>, w : Rc<RefCell<World>>, pos : &Vec2) -> Self {
-		if rand::gen_range(0., 1.) < 0.1 {
-			return Gobj::Scout(sq, w, *pos, *pos, 0., *pos)
-		}
-		Gobj::Ant(sq, w, *pos, *pos, 0., *pos, AntState::Wander(0., 0., 0.))
+		let caste = {
+			let world = &mut *w.borrow_mut();
+			let caste = world.hive.borrow().pick_caste(&mut world.rng);
+			caste
+		};
+		Self::new_ant_of(sq, w, pos, caste)
+	}
+	pub fn new_ant_of(sq : Rc<RefCell<Vec<Gobj>>>, w : Rc<RefCell<World>>, pos : &Vec2, caste : Caste) -> Self {
+		let pos = w.borrow().free_pos(pos);
+		Gobj::Ant(sq, w, pos, pos, pos, 0., pos, AntState::Wander(0., 0., 0.), caste, ANT_MAX_ENERGY, None)
+	}
+	pub fn new_queen(w : Rc<RefCell<World>>) -> Self {
+		let lay_interval = w.borrow().hive.borrow().brood_params.lay_interval;
+		Gobj::Queen(w, QUEEN_POS, lay_interval)
+	}
+	pub fn new_nurse(w : Rc<RefCell<World>>, pos : &Vec2) -> Self {
+		let pos = w.borrow().free_pos(pos);
+		Gobj::Nurse(w, pos, pos, Vec::new(), 0., 0.)
+	}
+	pub fn new_predator(sq : Rc<RefCell<Vec<Gobj>>>, w : Rc<RefCell<World>>, pos : &Vec2, kind : PredatorKind) -> Self {
+		let pos = w.borrow().free_pos(pos);
+		Gobj::Predator(sq, w, pos, pos, PredatorState::Patrol(pos), kind, Vec2::X, 0)
 	}
 	pub fn new_player(sq : Rc<RefCell<Vec<Gobj>>>, w : Rc<RefCell<World>>, pos : &Vec2) -> Self {
-		Gobj::Player(sq, w, *pos, Marker::Home(*pos, 0.), *pos, None)
+		let pos = w.borrow().free_pos(pos);
+		Gobj::Player(sq, w, pos, pos, Marker::Home(pos, 0.), pos, None, Vec2::ZERO, PLAYER_MAX_HEALTH, 0.)
 	}
-	pub fn new_particles(pos : &Vec2, count : usize, life : f32, radius : f32, col : Color, style : ParticleStyle) -> Self {
+	pub fn new_particles(pos : &Vec2, count : usize, life : f32, radius : f32, col : Color, style : ParticleStyle, rng : &mut Rng) -> Self {
 		let mut positions = Vec::new();
 		let mut velocities = Vec::new();
 		let mut lives = Vec::new();
 		match style {
 			ParticleStyle::Explosive(initial, _dissipation) =>
 				for _ in 0..count {
-					positions.push(random_ring_point(pos, 0., radius));
-					velocities.push(random_ring_point(&Vec2::ZERO, initial * 0.8, initial * 1.2));
-					lives.push(life * rand::gen_range(0.5, 1.));
+					positions.push(random_ring_point(pos, 0., radius, rng));
+					velocities.push(random_ring_point(&Vec2::ZERO, initial * 0.8, initial * 1.2, rng));
+					lives.push(life * rng.gen_range(0.5, 1.));
 				},
 			ParticleStyle::Orbit(force, _damp) =>
 				for _ in 0..count {
-					let p = random_ring_point(pos, 0., radius);
+					let p = random_ring_point(pos, 0., radius, rng);
 					positions.push(p);
-					velocities.push((p-*pos)*force*rand::gen_range(-0.8, 0.8));
-					lives.push(life * rand::gen_range(0.5, 1.));
+					velocities.push((p-*pos)*force*rng.gen_range(-0.8, 0.8));
+					lives.push(life * rng.gen_range(0.5, 1.));
 				},
 		}
 		Gobj::Particles(life, life, col, *pos, style, positions, velocities, lives)
 	}
 }
-impl Gobj {
-	fn translate_collidable(pos : &mut Vec2, delta : Vec2, world : Rc<RefCell<World>>) -> bool {
-		if world.borrow().is_collision(pos) {
-			panic!("Stuck agent at {pos}");
+impl ParticleStyle {
+	fn write(&self, w : &mut ByteWriter) {
+		let (tag, a, b) = match self {
+			ParticleStyle::Explosive(a, b) => (0, a, b),
+			ParticleStyle::Orbit(a, b) => (1, a, b),
+		};
+		w.u8(tag);
+		w.f32(*a);
+		w.f32(*b);
+	}
+	fn read(r : &mut ByteReader) -> io::Result<Self> {
+		let tag = r.u8()?;
+		let (a, b) = (r.f32()?, r.f32()?);
+		match tag {
+			0 => Ok(ParticleStyle::Explosive(a, b)),
+			1 => Ok(ParticleStyle::Orbit(a, b)),
+			t => Err(invalid_data(&format!("bad ParticleStyle tag {t}"))),
 		}
-		if !world.borrow().is_collision(&(*pos + delta)) {
-			*pos += delta;
-			true
+	}
+}
+impl Gobj {
+	/// The shared spawn queue and world aren't written; `read` takes them
+	/// from whoever owns the object set being restored.
+	pub fn write(&self, w : &mut ByteWriter) {
+		use Gobj::*;
+		match self {
+			Player(_, _, pos, prev_pos, marker_type, last_marker_pos, carried_food, facing, health, invulnerable) => {
+				w.u8(0);
+				w.vec2(pos);
+				w.vec2(prev_pos);
+				marker_type.write(w);
+				w.vec2(last_marker_pos);
+				w.option(carried_food, |w, f| f.write(w));
+				w.vec2(facing);
+				w.f32(*health);
+				w.f32(*invulnerable);
+			},
+			Ant(_, _, pos, prev_pos, target, target_change_cooldown, last_marker_pos, state, caste, energy, handoff) => {
+				w.u8(1);
+				w.vec2(pos);
+				w.vec2(prev_pos);
+				w.vec2(target);
+				w.f32(*target_change_cooldown);
+				w.vec2(last_marker_pos);
+				state.write(w);
+				caste.write(w);
+				w.f32(*energy);
+				w.option(handoff, |w, (from, left)| { w.vec2(from); w.f32(*left); });
+			},
+			Corpse(pos, facing, rad, life) => {
+				w.u8(5);
+				w.vec2(pos);
+				w.vec2(facing);
+				w.f32(*rad);
+				w.f32(*life);
+			},
+			Queen(_, pos, lay_timer) => {
+				w.u8(6);
+				w.vec2(pos);
+				w.f32(*lay_timer);
+			},
+			Nurse(_, pos, prev_pos, path, load, repath) => {
+				w.u8(7);
+				w.vec2(pos);
+				w.vec2(prev_pos);
+				w.seq(path, |w, p| w.vec2(p));
+				w.f32(*load);
+				w.f32(*repath);
+			},
+			Predator(_, _, pos, prev_pos, state, kind, facing, meals) => {
+				w.u8(8);
+				w.vec2(pos);
+				w.vec2(prev_pos);
+				state.write(w);
+				kind.write(w);
+				w.vec2(facing);
+				w.usize(*meals);
+			},
+			Particles(o_life, life, col, pos, style, poss, vels, lives) => {
+				w.u8(3);
+				w.f32(*o_life);
+				w.f32(*life);
+				w.color(col);
+				w.vec2(pos);
+				style.write(w);
+				w.seq(poss, |w, p| w.vec2(p));
+				w.seq(vels, |w, v| w.vec2(v));
+				w.seq(lives, |w, l| w.f32(*l));
+			},
+			Fader(left) => {
+				w.u8(4);
+				w.f32(*left);
+			},
 		}
-		else {
-			const DIRS : &[Vec2] = &[
-				Vec2::X, Vec2::Y, Vec2::NEG_X, Vec2::NEG_Y
-			];
-			let proj_delta = DIRS.iter()
-				.map(|d| delta*d.dot(delta))
-				.filter(|v| !world.borrow().is_collision(&(*pos+*v)))
-				.max_by(|v, w|
-					v.length().partial_cmp(&w.length()).unwrap());
-			if proj_delta.is_some() {
-				*pos += proj_delta.unwrap();
-				true
-			} else { false }
+	}
+	pub fn read(r : &mut ByteReader, sq : Rc<RefCell<Vec<Gobj>>>, world : Rc<RefCell<World>>) -> io::Result<Self> {
+		use Gobj::*;
+		Ok(match r.u8()? {
+			0 => Player(sq, world,
+				r.vec2()?, r.vec2()?, Marker::read(r)?, r.vec2()?,
+				r.option(Food::read)?, r.vec2()?, r.f32()?, r.f32()?),
+			1 => Ant(sq, world,
+				r.vec2()?, r.vec2()?, r.vec2()?, r.f32()?, r.vec2()?,
+				AntState::read(r)?, Caste::read(r)?, r.f32()?,
+				r.option(|r| Ok((r.vec2()?, r.f32()?)))?),
+			3 => Particles(
+				r.f32()?, r.f32()?, r.color()?, r.vec2()?,
+				ParticleStyle::read(r)?,
+				r.seq(|r| r.vec2())?,
+				r.seq(|r| r.vec2())?,
+				r.seq(|r| r.f32())?),
+			4 => Fader(r.f32()?),
+			5 => Corpse(r.vec2()?, r.vec2()?, r.f32()?, r.f32()?),
+			6 => Queen(world, r.vec2()?, r.f32()?),
+			7 => Nurse(world, r.vec2()?, r.vec2()?, r.seq(|r| r.vec2())?, r.f32()?, r.f32()?),
+			8 => Predator(sq, world,
+				r.vec2()?, r.vec2()?, PredatorState::read(r)?, PredatorKind::read(r)?,
+				r.vec2()?, r.usize()?),
+			t => return Err(invalid_data(&format!("bad Gobj tag {t}"))),
+		})
+	}
+	fn translate_collidable(pos : &mut Vec2, delta : Vec2, rad : f32, world : Rc<RefCell<World>>) -> bool {
+		if world.borrow().is_collision(pos) {
+			// spawned or shoved into a wall, pop out before trying to move
+			*pos = world.borrow().free_pos(pos);
 		}
+		let next = world.borrow().collision.slide_circle(pos, delta, rad);
+		// mostly blocked counts as a failed move, so ants turn away from walls
+		let moved = next.distance(*pos) > delta.length()*MIN_SLIDE_PROGRESS;
+		*pos = next;
+		moved
 	}
 }
 fn draw_ant(rd : &RenderData, pos : &Vec2, rad : f32, col : &Color, angle : &Vec2) {
@@ -110,44 +294,148 @@ fn draw_ant(rd : &RenderData, pos : &Vec2, rad : f32, col : &Color, angle : &Vec
 			flip_x,
 			..DrawTextureParams::default() });
 }
+impl Gobj {
+	/// Where an agent should be drawn this frame, between its last two steps.
+	pub fn render_pos(&self, rd : &RenderData) -> Option<Vec2> {
+		use Gobj::*;
+		match self {
+			Player(_, _, pos, prev_pos, ..) |
+			Ant(_, _, pos, prev_pos, ..) |
+			Nurse(_, pos, prev_pos, ..) |
+			Predator(_, _, pos, prev_pos, ..) => Some(rd.interp(prev_pos, pos)),
+			_ => None,
+		}
+	}
+	/// Where an ant is and the food it's carrying, if it's carried it far
+	/// enough to pass it on.
+	pub fn relay_food(&self) -> Option<(Vec2, &Food)> {
+		match self {
+			Gobj::Ant(_, _, pos, .., AntState::GoHome(food, .., carried), _, _, _)
+				if *carried >= ANT_RELAY_MIN_CARRY => Some((*pos, food)),
+			_ => None,
+		}
+	}
+	/// Whether this is an ant with nothing better to do that could carry `food` home.
+	pub fn can_take(&self, food : &Food) -> bool {
+		match self {
+			Gobj::Ant(.., state, caste, energy, _) =>
+				matches!(state, AntState::Wander(..) | AntState::Follow)
+				&& caste.params().carry >= food.value
+				&& *energy >= ANT_HUNGRY_ENERGY,
+			_ => false,
+		}
+	}
+	/// Leaves whatever food an ant was carrying where it is.
+	pub fn drop_food(&self) {
+		if let Gobj::Ant(_, world, pos, .., state, _, _, _) = self {
+			Self::drop_carried(world, pos, state);
+		}
+	}
+	fn drop_carried(world : &Rc<RefCell<World>>, pos : &Vec2, state : &AntState) {
+		if let AntState::GoHome(food, ..) = state {
+			let mut food = food.clone();
+			food.pos = world.borrow().free_pos(pos);
+			world.borrow_mut().food.put_food(food);
+		}
+	}
+	/// Makes the player drop whatever they're carrying, returning where they were.
+	/// They stay down until `revive_player`.
+	pub fn kill_player(&mut self) -> Option<Vec2> {
+		let Gobj::Player(_, world, pos, _, _, _, carried_food, ..) = self else { return None };
+		if let Some(mut food) = carried_food.take() {
+			food.pos = world.borrow().free_pos(pos);
+			world.borrow_mut().food.put_food(food);
+		}
+		world.borrow_mut().player_pos = None;
+		Some(*pos)
+	}
+	/// Back on full health, with a moment before anything can hurt them again.
+	pub fn revive_player(&mut self) {
+		if let Gobj::Player(.., health, invulnerable) = self {
+			*health = PLAYER_MAX_HEALTH;
+			*invulnerable = PLAYER_INVULNERABLE_TIME;
+		}
+	}
+	pub fn player_health(&self) -> Option<f32> {
+		match self {
+			Gobj::Player(.., health, _) => Some(*health),
+			_ => None,
+		}
+	}
+	/// Lets go of the food an ant's carrying home and sends it back out.
+	pub fn give_food(&mut self) -> Option<Food> {
+		let Gobj::Ant(_, world, .., state, _, _, _) = self else { return None };
+		let AntState::GoHome(food, .., carried) = state else { return None };
+		let food = food.clone();
+		let w = &mut *world.borrow_mut();
+		w.trips.record(*carried);
+		*state = AntState::Wander(0., random_angle(&mut w.rng), 0.);
+		Some(food)
+	}
+	/// Takes `food` off the ant at `from` to carry home.
+	pub fn receive_food(&mut self, food : Food, from : &Vec2) {
+		if let Gobj::Ant(.., state, _, _, handoff) = self {
+			*state = AntState::GoHome(food, 0., 0., 0., 0.);
+			*handoff = Some((*from, ANT_HANDOFF_TIME));
+		}
+	}
+}
 impl GameObject for Gobj {
-	fn update(&mut self) -> bool {
-		let d = get_frame_time();
+	fn update(&mut self, d : f32, input : &FrameInput) -> bool {
 		use Gobj::*;
 		match self {
-			Player(spawn_queue, world, pos, marker_type, last_marker_pos, carried_food) => {
+			Player(spawn_queue, world, pos, prev_pos, marker_type, last_marker_pos, carried_food, facing, health, invulnerable) => {
+				*prev_pos = *pos;
+				let hurt = std::mem::take(&mut world.borrow_mut().player_damage);
+				if *health <= 0. {
+					world.borrow_mut().player_pos = None;
+					return true;
+				}
+				if *invulnerable > 0. {
+					*invulnerable -= d;
+				} else if hurt > 0. {
+					*health -= hurt;
+					*invulnerable = PLAYER_INVULNERABLE_TIME;
+				}
 				if pos.distance(*last_marker_pos) > ANT_MARKER_DIST {
+					// alarms are raised on their own key, the trail is only ever home or food
 					match marker_type {
-						Marker::Home(..) => world.borrow_mut().marker
-							.create_marker(Marker::Home(*pos, HOME_MARKER_LIFE), spawn_queue.clone()),
-						Marker::Food(..) => world.borrow_mut().marker
-							.create_marker(Marker::Food(*pos, FOOD_MARKER_LIFE), spawn_queue.clone()),
+						Marker::Home(..) => world.borrow_mut()
+							.create_marker(Marker::Home(*pos, MARKER_DEPOSIT), spawn_queue.clone()),
+						Marker::Food(..) | Marker::Alarm(..) => world.borrow_mut()
+							.create_marker(Marker::Food(*pos, MARKER_DEPOSIT), spawn_queue.clone()),
 					}
 					*last_marker_pos = *pos;
 				}
-				if is_key_pressed(KeyCode::Space) {
+				if input.toggle_marker {
 					*marker_type = match marker_type {
 						Marker::Home(..) => Marker::Food(*pos, 0.),
-						Marker::Food(..) => Marker::Home(*pos, 0.),
+						Marker::Food(..) | Marker::Alarm(..) => Marker::Home(*pos, 0.),
 					}
 				}
-				let iv = get_ivn();
-				Self::translate_collidable(pos, iv*d*PLAYER_SPEED, world.clone());
+				if input.alarm {
+					world.borrow_mut().raise_alarm(pos, ALARM_DEPOSIT, spawn_queue.clone());
+				}
+				let iv = input.ivn();
+				*facing = iv;
+				Self::translate_collidable(pos, iv*d*PLAYER_SPEED, PLAYER_RAD, world.clone());
+				world.borrow_mut().player_pos = Some(*pos);
 
 				let closest_food_id = world.borrow().food.find_food(pos, &iv);
-				let get_closest_food_pos = ||
-					world
-					.borrow()
-					.food
-					.get_food(closest_food_id.unwrap())
-					.expect("Closest food no longer exists")
-					.pos;
+				// from the edge of the food, big sources are reached before their centre
+				let get_closest_food_dist = || {
+					let w = world.borrow();
+					let f = w.food
+						.get_food(closest_food_id.unwrap())
+						.expect("Closest food no longer exists");
+					f.pos.distance(*pos) - f.radius()
+				};
 				if closest_food_id.is_some()
 					&& carried_food.is_none()
-					&& get_closest_food_pos().distance(*pos) < PLAYER_PICKUP_RANGE {
+					&& get_closest_food_dist() < PLAYER_PICKUP_RANGE {
 					*carried_food = world.borrow_mut()
 						.food
-						.take_food(closest_food_id.unwrap());
+						.take_bite(closest_food_id.unwrap(), PLAYER_CARRY);
 				}
 				else if carried_food.is_some()
 					&& pos.distance(HOME_POS) < ANT_HOME_DEPOSIT_RANGE {
@@ -160,19 +448,31 @@ impl GameObject for Gobj {
 					let cfp = carried_food.clone().unwrap().pos;
 					carried_food.as_mut().unwrap().pos = lerp(
 						cfp,
-						*pos + get_ivn()*PLAYER_RAD,
+						*pos + iv*PLAYER_RAD,
 						d*20.
 					);
 				}
 				true
 			},
-			Ant(spawn_queue, world, pos, target, target_change_cooldown, last_marker_pos, state) => {
+			Ant(spawn_queue, world, pos, prev_pos, target, target_change_cooldown, last_marker_pos, state, caste, energy, handoff) => {
+				*prev_pos = *pos;
+				if let Some((_, left)) = handoff {
+					*left -= d;
+					if *left <= 0. { *handoff = None }
+				}
+				let caste = caste.params();
+				let at_home = pos.distance(HOME_POS) < ANT_HOME_DEPOSIT_RANGE;
+				if at_home {
+					let fed = world.borrow().hive.borrow_mut().feed(ANT_MAX_ENERGY - *energy);
+					*energy += fed;
+				}
 				let heading = *target - *pos;
 				let heading =
 					if heading.length() != 0.0 { heading.normalize() }
 					else { heading };
 
 				use AntState::*;
+				let rand_angle = || random_angle(&mut world.borrow_mut().rng);
 				let wander = |time_left_until_next_angle : &mut f32, a : &mut f32, avel : &mut f32, target : &mut Vec2| {
 						let rtarget = *target - *pos;
 						let angle_target = rtarget.y.atan2(rtarget.x);
@@ -182,154 +482,317 @@ impl GameObject for Gobj {
 						*target += vec2(avel.cos(), avel.sin())*ANT_TURN_SPEED*d;
 						*time_left_until_next_angle -= d;
 						if *time_left_until_next_angle < 0. {
-							*time_left_until_next_angle = rand::gen_range(1., 3.);
-							*avel = rand::gen_range(-PI, PI);
-							*a = rand::gen_range(-PI, PI)*2.;
+							let rng = &mut world.borrow_mut().rng;
+							*time_left_until_next_angle = rng.gen_range(1., 3.);
+							*avel = rng.gen_range(-PI, PI);
+							*a = rng.gen_range(-PI, PI)*2.;
 						}
 					};
 
 				let mut next_marker = None;
-				let closest_marker_food = world.borrow().marker
-					.local_markers(
-						pos,
-						&heading,
-						&|m| match *m { Marker::Food(..) => true, _ => false })
-					.iter()
-					.map(|m| m.clone())
-					.filter(|m| m.pos().distance(*pos) > ANT_MARKER_DIST)
-					.min_by(|a, b| match a.pos().distance(*pos) < b.pos().distance(*pos) {
-							true => std::cmp::Ordering::Less,
-							false => std::cmp::Ordering::Greater,
-						}
-					);
-				let closest_marker_home = world.borrow().marker
-					.local_markers(
-						pos,
-						&heading,
-						&|m| match *m { Marker::Home(..) => true, _ => false })
-					.iter()
-					.map(|m| m.clone())
-					.filter(|m| m.pos().distance(*pos) > ANT_MARKER_DIST)
-					.min_by(|a, b| match a.pos().distance(HOME_POS) < b.pos().distance(HOME_POS) {
-							true => std::cmp::Ordering::Less,
-							false => std::cmp::Ordering::Greater,
-						}
-					);
+				let forages = caste.carry > 0.;
+				let food_trail = world.borrow().sense(Pheromone::Food, pos, &heading, &caste.antennae)
+					.filter(|_| forages);
+				let home_trail = world.borrow().sense(Pheromone::Home, pos, &heading, &caste.antennae);
 
 				let closest_food_id = world.borrow().food.find_food(pos, &heading);
-				let get_closest_food_pos = ||
-					world.borrow().food
-					.get_food(closest_food_id.unwrap())
-					.expect("Closest food no longer exists")
-					.pos;
+				// only worth going for if it's a forager and there's no wall in the way
+				let food_in_reach = closest_food_id
+					.and_then(|id| world.borrow().food.get_food(id).cloned())
+					.filter(|f| forages
+						&& f.pos.distance(*pos) < ANT_FOOD_DETECTION_RANGE
+						&& world.borrow().reachable(pos, &f.pos))
+					.is_some();
+
+				if *energy < ANT_HUNGRY_ENERGY && matches!(state, Wander(..) | Follow | GetFood(..)) {
+					*state = Hungry;
+				}
+				let alarm = world.borrow().sense(Pheromone::Alarm, pos, &heading, &caste.antennae);
+				if alarm.is_some() && matches!(state, Wander(..) | Follow | GetFood(..) | Alarmed(..)) {
+					*state = Alarmed(ANT_ALARM_TIME);
+				}
 
 				let mut next_target = target.clone();
 				*state = match state {
 					Wander(time_left_until_next_angle, a, avel) => {
-						next_marker = Some(Marker::Home(*pos, HOME_MARKER_LIFE));
+						next_marker = Some(Marker::Home(*pos, MARKER_DEPOSIT));
 						wander(time_left_until_next_angle, a, avel, target);
+						// strayed past its patrol, turn back towards home
+						if pos.distance(HOME_POS) > caste.patrol_radius {
+							if let Some(dir) = world.borrow().home_direction(pos) {
+								*target = *pos + dir;
+							}
+						}
 
-						if closest_food_id.is_some() &&
-							get_closest_food_pos().distance(*pos)
-								< ANT_FOOD_DETECTION_RANGE
-						{
-							next_marker = Some(Marker::Food(*pos, FOOD_MARKER_LIFE));
-							GetFood(get_closest_food_pos())
-						} else if closest_marker_food.is_some() {
-							Follow(closest_marker_food)
+						if food_in_reach {
+							next_marker = Some(Marker::Food(*pos, MARKER_DEPOSIT));
+							GetFood(closest_food_id.unwrap())
+						} else if food_trail.is_some() {
+							Follow
 						}
 						else {
 							state.clone()
 						}
 					},
-					GetFood(food_pos) => {
-						next_target = *food_pos;
-						if closest_food_id.is_none() {
-							Wander(0., random_angle(), 0.)
-						} else {
-							next_marker = Some(Marker::Food(*pos, FOOD_MARKER_LIFE));
-							if food_pos.distance(*pos) < ANT_FOOD_PICKUP_RANGE {
-								let f = world.borrow_mut()
-									.food
-									.take_food(closest_food_id.unwrap());
-								match f {
-									Some(f) => GoHome(f, closest_marker_home, 0.,0.,0.),
-									None => Wander(0.,random_angle(),0.)
+					GetFood(food_id) => {
+						let food = world.borrow().food.get_food(*food_id).map(|f| (f.pos, f.radius()));
+						match food {
+							// someone else got to it first
+							None => Wander(0., rand_angle(), 0.),
+							Some((food_pos, food_rad)) => {
+								next_target = food_pos;
+								next_marker = Some(Marker::Food(*pos, MARKER_DEPOSIT));
+								if food_pos.distance(*pos) < ANT_FOOD_PICKUP_RANGE + food_rad {
+									let f = world.borrow_mut()
+										.food
+										.take_bite(*food_id, caste.carry);
+									match f {
+										Some(f) => GoHome(f, 0.,0.,0.,0.),
+										None => Wander(0.,rand_angle(),0.)
+									}
+								} else {
+									state.clone()
 								}
-							} else {
-								state.clone()
 							}
 						}
 					},
-					Follow(m) => {
-						if closest_food_id.is_some() &&
-							get_closest_food_pos().distance(*pos)
-								< ANT_FOOD_DETECTION_RANGE
-						{
-							next_marker = Some(Marker::Food(*pos, FOOD_MARKER_LIFE));
-							GetFood(get_closest_food_pos())
+					Follow => {
+						if food_in_reach {
+							next_marker = Some(Marker::Food(*pos, MARKER_DEPOSIT));
+							GetFood(closest_food_id.unwrap())
 						} else {
-							next_marker = Some(Marker::Home(*pos, HOME_MARKER_LIFE));
-							match m {
-								Some(Marker::Food(p, ..)) => {
-									next_target = *p;
-									Follow(closest_marker_food)
+							next_marker = Some(Marker::Home(*pos, MARKER_DEPOSIT));
+							match food_trail {
+								Some(dir) => {
+									next_target = *pos + dir*ANT_MARKER_DIST;
+									Follow
 								},
-								_ => Wander(0., random_angle(), 0.)
+								None => Wander(0., rand_angle(), 0.)
 							}
 						}
 					},
-					GoHome(food, m, time_left_until_next_angle, a, avel) => {
-						next_marker = Some(Marker::Food(*pos, FOOD_MARKER_LIFE));
+					GoHome(food, time_left_until_next_angle, a, avel, carried) => {
+						next_marker = Some(Marker::Food(*pos, MARKER_DEPOSIT));
 						if pos.distance(HOME_POS) < ANT_HOME_DEPOSIT_RANGE {
 							world.borrow_mut()
 								.hive.borrow_mut()
 								.deposit(false, food.value);
-							Wander(0., random_angle(), 0.)
+							world.borrow_mut().trips.record(*carried);
+							Wander(0., rand_angle(), 0.)
 						}
 						else {
-							let nm =
-								if closest_marker_home.is_some()
-									{ closest_marker_home }
-								else { closest_marker_food };
-							if m.is_some() { next_target = *m.clone().unwrap().pos(); }
-							GoHome(food.clone(), nm, *time_left_until_next_angle, *a, *avel)
+							// the home trail's mostly wander trails, so go by the map and
+							// only fall back on it off the map's grid
+							if let Some(dir) = world.borrow().home_direction(pos).or(home_trail) {
+								next_target = *pos + dir*ANT_MARKER_DIST;
+							}
+							GoHome(food.clone(), *time_left_until_next_angle, *a, *avel, *carried)
+						}
+					},
+					Alarmed(left) => {
+						let dir = match (alarm, caste.alarm) {
+							(Some(dir), AlarmResponse::Flee) => -dir,
+							(Some(dir), AlarmResponse::Gather) => {
+								let w = world.borrow();
+								let relay = w.smell(Pheromone::Alarm, &(*pos + dir*caste.antennae.distance), caste.antennae.radius)*ANT_ALARM_RELAY;
+								// only where it's weaker than what it'd lay, or a crowd
+								// gathered on the alarm would keep topping it up
+								if relay >= ANT_ALARM_RELAY_MIN && w.smell(Pheromone::Alarm, pos, MARKER_MERGE_RADIUS) < relay {
+									next_marker = Some(Marker::Alarm(*pos, relay));
+								}
+								dir
+							},
+							// lost the smell, keep going the way it was
+							(None, _) => heading,
+						};
+						next_target = *pos + dir*ANT_MARKER_DIST;
+						*left -= d;
+						if *left <= 0. { Wander(0., rand_angle(), 0.) } else { Alarmed(*left) }
+					},
+					Hungry => {
+						if at_home && *energy >= ANT_HUNGRY_ENERGY {
+							Wander(0., rand_angle(), 0.)
+						} else {
+							// wanderers lay home trails everywhere, only the map's sure to lead home
+							if let Some(dir) = world.borrow().home_direction(pos).or(home_trail) {
+								next_target = *pos + dir*ANT_MARKER_DIST;
+							}
+							Hungry
 						}
 					},
 				};
 				*target_change_cooldown -= d;
 				if *target_change_cooldown < 0. {
 					*target = next_target;
-					*target_change_cooldown = rand::gen_range(0.0, 0.1) + ANT_MARKER_DIST/ANT_SPEED;
+					*target_change_cooldown = world.borrow_mut().rng.gen_range(0.0, 0.1) + ANT_MARKER_DIST/caste.speed;
 				}
 
 				if pos != target {
-					if !Self::translate_collidable(pos, (*target - *pos).normalize()*ANT_SPEED*d, world.clone()) {
+					if !Self::translate_collidable(pos, (*target - *pos).normalize()*caste.speed*d, caste.radius, world.clone()) {
 						*target = *pos-heading;
 					}
 				}
+				if let GoHome(.., carried) = state {
+					*carried += pos.distance(*prev_pos);
+				}
+				let next_marker = match (caste.marking, next_marker) {
+					// passing on an alarm matters more than how a caste usually marks
+					(_, Some(m @ Marker::Alarm(..))) => Some(m),
+					(Marking::Trail, m) => m,
+					(Marking::HomeOnly, _) => Some(Marker::Home(*pos, MARKER_DEPOSIT)),
+					(Marking::Off, _) => None,
+				};
 				if last_marker_pos.distance(*pos) > ANT_MARKER_DIST && next_marker.is_some() {
-					world.borrow_mut().marker.create_marker(next_marker.unwrap(), spawn_queue.clone());
+					world.borrow_mut().create_marker(next_marker.unwrap(), spawn_queue.clone());
 					*last_marker_pos = *pos;
 				}
+
+				*energy -= ANT_IDLE_DRAIN*d + pos.distance(*prev_pos)*ANT_WALK_DRAIN;
+				if *energy <= 0. {
+					// whatever it was carrying is left where it fell
+					Self::drop_carried(world, pos, state);
+					spawn_queue.borrow_mut().push(Corpse(*pos, heading, caste.radius, CORPSE_LIFE));
+					return false;
+				}
 				true
 			},
-			Scout(spawn_queue, world, pos, target, target_change_cooldown, last_marker_pos) => {
-				if pos.x >  HARD_BOUNDS.x { pos.x =  HARD_BOUNDS.x; }
-				if pos.x < -HARD_BOUNDS.x { pos.x = -HARD_BOUNDS.x; }
-				if pos.y >  HARD_BOUNDS.y { pos.y = HARD_BOUNDS.y; }
-				if pos.y < -HARD_BOUNDS.y { pos.y = -HARD_BOUNDS.y; }
-				*target_change_cooldown -= d;
-				if *target_change_cooldown < 0. {
-					*target = random_ring_point(pos, ANT_MARKER_DIST*3., ANT_MARKER_DIST*10.);
-					*target_change_cooldown = ANT_MARKER_DIST/ANT_SPEED;
+			Corpse(.., life) => {
+				*life -= d;
+				*life > 0.
+			},
+			Predator(spawn_queue, world, pos, prev_pos, state, kind, facing, meals) => {
+				*prev_pos = *pos;
+				let kind = kind.params();
+				use PredatorState::*;
+				// an alarm right on top of it is enough to send it packing
+				if !matches!(state, Retreat(..))
+					&& world.borrow().smell(Pheromone::Alarm, pos, kind.radius*2.) >= PREDATOR_SCARE_SMELL {
+					*state = Retreat(PREDATOR_RETREAT_TIME);
 				}
-				if pos != target {
-					Self::translate_collidable(pos, (*target - *pos).normalize()*ANT_SPEED*d, world.clone());
+				let locate = |prey : Prey| {
+					let w = world.borrow();
+					match prey {
+						Prey::Player => w.player_pos,
+						Prey::Ant(id) => w.ants_near(pos, kind.notice_range*2.)
+							.find(|(_, a)| *a == id)
+							.map(|(p, _)| *p),
+					}
+				};
+				let spot = || {
+					let w = world.borrow();
+					let player = w.player_pos.map(|p| (p.distance(*pos), Prey::Player));
+					w.ants_near(pos, kind.notice_range)
+						.map(|(p, id)| (p.distance(*pos), Prey::Ant(*id)))
+						.chain(player)
+						.filter(|(dist, prey)| *dist < kind.notice_range
+							&& locate(*prey).is_some_and(|p| w.reachable(pos, &p)))
+						.min_by(|a, b| a.0.total_cmp(&b.0))
+						.map(|(_, prey)| prey)
+				};
+
+				let mut goal = None;
+				let mut speed = kind.speed;
+				*state = match *state {
+					Patrol(to) => {
+						if let Some(prey) = spot() {
+							Notice(prey, PREDATOR_NOTICE_TIME)
+						} else if pos.distance(to) < kind.radius {
+							Patrol(world.borrow_mut().random_free_pos(MAP_DIMS.x*0.1, MAP_DIMS.x*0.45))
+						} else {
+							goal = Some(to);
+							Patrol(to)
+						}
+					},
+					Notice(prey, left) => match locate(prey) {
+						Some(p) if p.distance(*pos) < kind.notice_range*1.5 => {
+							*facing = (p - *pos).normalize_or_zero();
+							if left <= d { Chase(prey, PREDATOR_CHASE_TIME) } else { Notice(prey, left - d) }
+						},
+						_ => Patrol(*pos),
+					},
+					Chase(prey, left) => match locate(prey) {
+						Some(p) if left > 0. => {
+							speed = kind.chase_speed;
+							goal = Some(p);
+							if p.distance(*pos) < kind.radius + ANT_RAD {
+								let mut w = world.borrow_mut();
+								match prey {
+									Prey::Ant(id) => {
+										w.eaten.push(id);
+										w.raise_alarm(&p, MARKER_DEPOSIT*2., spawn_queue.clone());
+										*meals += 1;
+									},
+									Prey::Player => w.player_damage += kind.bite,
+								}
+								if *meals >= kind.meals { Retreat(PREDATOR_RETREAT_TIME) } else { Patrol(*pos) }
+							} else {
+								Chase(prey, left - d)
+							}
+						},
+						// it got away
+						_ => Patrol(*pos),
+					},
+					Retreat(left) => {
+						if left <= 0. { return false }
+						speed = kind.chase_speed;
+						goal = Some(*pos + (*pos - HOME_POS).try_normalize().unwrap_or(Vec2::X)*kind.radius);
+						Retreat(left - d)
+					},
+				};
+				if let Some(goal) = goal {
+					let dir = (goal - *pos).normalize_or_zero();
+					*facing = dir;
+					let moved = Self::translate_collidable(pos, dir*speed*d, kind.radius, world.clone());
+					// walked into a wall, somewhere else will do
+					if !moved && matches!(state, Patrol(..)) {
+						*state = Patrol(*pos);
+					}
 				}
-				if last_marker_pos.distance(*pos) > ANT_MARKER_DIST {
-					world.borrow_mut().marker.create_marker(Marker::Home(*pos, HOME_MARKER_LIFE), spawn_queue.clone());
-					*last_marker_pos = *pos;
+				true
+			},
+			Queen(world, _, lay_timer) => {
+				let world = world.borrow();
+				let mut hive = world.hive.borrow_mut();
+				hive.queen_food = (hive.queen_food - QUEEN_APPETITE*d).max(0.);
+				// the fuller the stores, the faster she lays
+				*lay_timer -= d*hive.state_as_float().max(0.);
+				if *lay_timer <= 0. {
+					*lay_timer = hive.brood_params.lay_interval;
+					hive.lay_egg();
+				}
+				true
+			},
+			Nurse(world, pos, prev_pos, path, load, repath) => {
+				*prev_pos = *pos;
+				if *load > 0. && pos.distance(QUEEN_POS) < QUEEN_FEED_RANGE {
+					let w = world.borrow();
+					let mut hive = w.hive.borrow_mut();
+					hive.queen_food = (hive.queen_food + *load).min(QUEEN_MAX_FOOD);
+					*load = 0.;
+					path.clear();
+				} else if *load == 0. && pos.distance(NEST_STORE) < NURSE_STORE_RANGE {
+					let w = world.borrow();
+					let mut hive = w.hive.borrow_mut();
+					// wait for her to be able to eat a whole load, not walk over with crumbs
+					if hive.queen_hunger() < NURSE_LOAD { return true }
+					*load = hive.take(NURSE_LOAD);
+					path.clear();
+					if *load == 0. { return true }
+				}
+				*repath = (*repath - d).max(0.);
+				if path.is_empty() && *repath <= 0. {
+					let goal = if *load > 0. { QUEEN_POS } else { NEST_STORE };
+					match world.borrow().find_path(pos, &goal) {
+						Some(p) => *path = p,
+						None => *repath = NURSE_REPATH_TIME,
+					}
+				}
+				if let Some(next) = path.first().copied() {
+					if pos.distance(next) < NURSE_WAYPOINT_RANGE {
+						path.remove(0);
+					} else {
+						Self::translate_collidable(pos, (next - *pos).normalize()*ANT_SPEED*d, ANT_RAD, world.clone());
+					}
 				}
 				true
 			},
@@ -361,45 +824,91 @@ impl GameObject for Gobj {
 	fn render(&self, rd : &RenderData) {
 		use Gobj::*;
 		match self {
-			Player(_, _, pos, marker_type, _, carried_food) => {
-				let col = match marker_type {
-					Marker::Home(..) => COL_MARKER_HOME,
-					Marker::Food(..) => COL_MARKER_FOOD,
-				};
-				let pos = rd.cast_pos(pos);
+			Player(_, _, pos, prev_pos, marker_type, _, carried_food, facing, health, invulnerable) => {
+				if *health <= 0. { return }
+				let col = marker_type.pheromone().colour();
+				let pos = rd.cast_pos(&rd.interp(prev_pos, pos));
 				let s = rd.scale_unit(PLAYER_RAD);
-				draw_ant(rd, &pos, s, &col, &get_ivn());
+				// blink while invulnerable
+				if *invulnerable <= 0. || (*invulnerable*10.) as i32 % 2 == 0 {
+					draw_ant(rd, &pos, s, &col, facing);
+				}
+				if *health < PLAYER_MAX_HEALTH {
+					for i in 0..health.ceil() as i32 {
+						let p = pos + vec2((i as f32 - (*health - 1.)/2.)*s, -s*2.);
+						draw_circle(p.x, p.y, s*0.3, COL_MARKER_ALARM);
+					}
+				}
 				if carried_food.is_some() {
 					carried_food.clone().unwrap().render(rd);
 				}
 			},
-			Ant(_sq, _w, pos, target, _tcc, _lmp, state) => {
+			Ant(_sq, _w, pos, prev_pos, target, _tcc, _lmp, state, caste, _energy, handoff) => {
+				let caste = caste.params();
+				let heading = (*target-*pos).normalize();
+				let pos = &rd.interp(prev_pos, pos);
 				let mut carried_food = None;
 				let col = match state {
 					AntState::Wander(..) => COL_MARKER_HOME,
-					AntState::Follow(..) => LIGHTGRAY,
+					AntState::Follow => LIGHTGRAY,
 					AntState::GetFood(..) => LIGHTGRAY,
+					AntState::Hungry => GRAY,
+					AntState::Alarmed(..) => COL_MARKER_ALARM,
 					AntState::GoHome(food, ..) => {
 						carried_food = Some(food.clone());
 						carried_food.as_mut().unwrap().pos = *pos;
 						 COL_MARKER_FOOD
 					},
 				};
+				let t = caste.tint;
+				let col = Color::new(col.r*t.r, col.g*t.g, col.b*t.b, col.a*t.a);
 
-				let heading = (*target-*pos).normalize();
 				let pos = rd.cast_pos(pos);
-				let s = rd.scale_unit(ANT_RAD);
+				let s = rd.scale_unit(caste.radius);
+				// a fading line back to the ant that passed it the food
+				if let Some((from, left)) = handoff {
+					let from = rd.cast_pos(from);
+					let mut link = COL_MARKER_FOOD;
+					link.a = left/ANT_HANDOFF_TIME;
+					draw_line(from.x, from.y, pos.x, pos.y, rd.scale_unit(1.), link);
+					draw_circle(from.x, from.y, s*0.6, link);
+				}
 				draw_ant(rd, &pos, s, &col, &heading);
 				if carried_food.is_some() {
 					carried_food.clone().unwrap().render(rd);
 				}
 			},
-			Scout(_ow, _mw, pos, target, _tcc, _lmp) => {
-				let heading = (*target-*pos).normalize();
-				let col = COL_MARKER_HOME;
-				let pos = rd.cast_pos(pos);
-				let s = rd.scale_unit(ANT_RAD*0.8);
-				draw_ant(rd, &pos, s, &col, &heading);
+			Queen(world, pos, _) => {
+				let fullness = world.borrow().hive.borrow().queen_food/QUEEN_MAX_FOOD;
+				let col = Color::new(
+					lerp(GRAY.r, COL_QUEEN.r, fullness),
+					lerp(GRAY.g, COL_QUEEN.g, fullness),
+					lerp(GRAY.b, COL_QUEEN.b, fullness),
+					1.);
+				draw_ant(rd, &rd.cast_pos(pos), rd.scale_unit(QUEEN_RAD), &col, &-Vec2::X);
+			},
+			Nurse(_, pos, prev_pos, path, load, _) => {
+				let heading = path.first().map_or(Vec2::Y, |p| (*p - *pos).normalize_or_zero());
+				let p = rd.cast_pos(&rd.interp(prev_pos, pos));
+				draw_ant(rd, &p, rd.scale_unit(ANT_RAD), &COL_MARKER_HOME, &heading);
+				if *load > 0. {
+					let f = p + heading*rd.scale_unit(ANT_RAD);
+					draw_circle(f.x, f.y, rd.scale_unit(ANT_RAD*0.5), COL_MARKER_FOOD);
+				}
+			},
+			Predator(_, _, pos, prev_pos, state, kind, facing, _) => {
+				let kind = kind.params();
+				let mut col = kind.tint;
+				if let PredatorState::Retreat(left) = state {
+					col.a = left.min(1.);
+				}
+				let p = rd.cast_pos(&rd.interp(prev_pos, pos));
+				draw_ant(rd, &p, rd.scale_unit(kind.radius), &col, facing);
+			},
+			Corpse(pos, facing, rad, life) => {
+				let mut col = COL_CORPSE;
+				col.a = (*life/CORPSE_LIFE).min(1.);
+				draw_ant(rd, &rd.cast_pos(pos), rd.scale_unit(*rad), &col, facing);
 			},
 			Particles(o_life, _life, col, _pos, _style, poss, _vels, lives) => {
 				for i in 0..poss.len() {
@@ -1,22 +1,83 @@
-use macroquad::texture::Texture2D;
+use std::io;
 
-use crate::prelude::Assets;
+use macroquad::texture::Texture2D;
 
+use crate::{prelude::{Assets, ByteWriter, ByteReader, Rng, invalid_data}, ants::Caste};
 
+/// An egg or larva, with how long it has left before moving on.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Brood {
+	Egg(f32),
+	Larva(f32),
+}
+impl Brood {
+	pub fn write(&self, w : &mut ByteWriter) {
+		match self {
+			Brood::Egg(left) => { w.u8(0); w.f32(*left); },
+			Brood::Larva(left) => { w.u8(1); w.f32(*left); },
+		}
+	}
+	pub fn read(r : &mut ByteReader) -> io::Result<Self> {
+		Ok(match r.u8()? {
+			0 => Brood::Egg(r.f32()?),
+			1 => Brood::Larva(r.f32()?),
+			t => return Err(invalid_data(&format!("bad Brood tag {t}"))),
+		})
+	}
+}
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BroodParams {
+	/// Hive life spent on each egg.
+	pub egg_cost : f32,
+	/// Seconds between eggs with a full hive; an emptier hive lays slower.
+	pub lay_interval : f32,
+	/// Fraction of `HIVE_MAX_LIFE` below which nothing gets laid.
+	pub lay_threshold : f32,
+	pub egg_time : f32,
+	pub larva_time : f32,
+	/// Hive life each larva eats per second. Unfed larvae stop growing.
+	pub larva_upkeep : f32,
+	pub max_brood : usize,
+}
+pub const DEFAULT_BROOD : BroodParams = BroodParams {
+	egg_cost: 5.,
+	lay_interval: 4.,
+	lay_threshold: 0.3,
+	egg_time: 15.,
+	larva_time: 20.,
+	larva_upkeep: 0.05,
+	max_brood: 24,
+};
 
 #[derive(Debug)]
 pub struct Hive {
 	pub life : f32,
 	player_gave : bool,
+	player_died : bool,
+	/// Relative chance of each of `CASTES` for newly spawned ants.
+	pub caste_weights : [f32; 3],
+	pub brood : Vec<Brood>,
+	pub brood_params : BroodParams,
+	/// What the queen has left to eat; she starves at 0.
+	pub queen_food : f32,
 }
 
 pub const HIVE_MAX_LIFE : f32 = 500.;
 pub const HIVE_START_LIFE : f32 = HIVE_MAX_LIFE/2.;
+/// Ant energy bought by one point of hive life.
+const ENERGY_PER_LIFE : f32 = 100.;
+pub const QUEEN_MAX_FOOD : f32 = 60.;
+pub const DEFAULT_CASTE_WEIGHTS : [f32; 3] = [0.8, 0.1, 0.1];
 impl Hive {
 	pub fn new() -> Self {
 		Hive {
 			life: HIVE_START_LIFE,
 			player_gave : false,
+			player_died: false,
+			caste_weights: DEFAULT_CASTE_WEIGHTS,
+			brood: Vec::new(),
+			brood_params: DEFAULT_BROOD,
+			queen_food: QUEEN_MAX_FOOD,
 		}
 	}
 	pub fn deposit(&mut self, is_player : bool, value : f32) {
@@ -24,18 +85,88 @@ impl Hive {
 			self.player_gave = true;
 		}
 		self.life += value*5.;
-		self.life = self.life.max(HIVE_MAX_LIFE);
+		self.life = self.life.min(HIVE_MAX_LIFE);
+	}
+	/// Feeds an ant up to `wanted` energy out of the stores, returning how much it got.
+	pub fn feed(&mut self, wanted : f32) -> f32 {
+		let given = wanted.max(0.).min(self.life.max(0.)*ENERGY_PER_LIFE);
+		self.life -= given/ENERGY_PER_LIFE;
+		given
 	}
-	pub fn update(&mut self, d : f32) {
+	/// Returns how many new ants emerged from the brood.
+	pub fn update(&mut self, d : f32) -> usize {
 		self.life -= d;
+		self.grow_brood(d)
+	}
+	/// Takes up to `wanted` out of the stores.
+	pub fn take(&mut self, wanted : f32) -> f32 {
+		let taken = wanted.max(0.).min(self.life.max(0.));
+		self.life -= taken;
+		taken
+	}
+	/// Feeds the queen out of the stores, up to what she can eat.
+	pub fn feed_queen(&mut self, wanted : f32) -> f32 {
+		let given = self.take(wanted.min(self.queen_hunger()));
+		self.queen_food += given;
+		given
 	}
-	pub fn lost(&self) -> bool { self.life < 0.0 }
+	pub fn queen_hunger(&self) -> f32 { QUEEN_MAX_FOOD - self.queen_food }
+	/// Spends `egg_cost` on a new egg if the hive is fed well enough and there's room.
+	pub fn lay_egg(&mut self) -> bool {
+		let p = &self.brood_params;
+		if self.state_as_float() < p.lay_threshold || self.brood.len() >= p.max_brood {
+			return false;
+		}
+		self.life -= p.egg_cost;
+		self.brood.push(Brood::Egg(p.egg_time));
+		true
+	}
+	fn grow_brood(&mut self, d : f32) -> usize {
+		let p = self.brood_params;
+		let larvae = self.brood.iter().filter(|b| matches!(b, Brood::Larva(_))).count();
+		let upkeep = larvae as f32*p.larva_upkeep*d;
+		let fed = self.life > upkeep;
+		if fed {
+			self.life -= upkeep;
+		}
+		let mut emerged = 0;
+		self.brood.retain_mut(|b| match b {
+			Brood::Egg(left) => {
+				*left -= d;
+				if *left <= 0. {
+					*b = Brood::Larva(p.larva_time);
+				}
+				true
+			},
+			Brood::Larva(left) => {
+				if fed {
+					*left -= d;
+				}
+				if *left <= 0. {
+					emerged += 1;
+					false
+				} else { true }
+			},
+		});
+		emerged
+	}
+	pub fn lost(&self) -> bool { self.life < 0.0 || self.queen_food <= 0.0 }
 	pub fn did_player_give(&mut self) -> bool {
 		if self.player_gave {
 			self.player_gave = false;
 			true
 		} else { false }
 	}
+	/// Lets the nest know the player's coming back from the dead, not in from the surface.
+	pub fn player_died(&mut self) {
+		self.player_died = true;
+	}
+	pub fn did_player_die(&mut self) -> bool {
+		std::mem::take(&mut self.player_died)
+	}
+	pub fn pick_caste(&self, rng : &mut Rng) -> Caste {
+		Caste::pick(&self.caste_weights, rng)
+	}
 	pub fn state_as_tex(&self, a : &Assets) -> Texture2D {
 		let i = (
 				(self.life / HIVE_MAX_LIFE) *
@@ -44,4 +175,42 @@ impl Hive {
 		a.tex_hive_food[i]
 	}
 	pub fn state_as_float(&self) -> f32 { self.life / HIVE_MAX_LIFE }
+
+	pub fn write(&self, w : &mut ByteWriter) {
+		w.f32(self.life);
+		w.bool(self.player_gave);
+		w.bool(self.player_died);
+		for c in self.caste_weights {
+			w.f32(c);
+		}
+		w.seq(&self.brood, |w, b| b.write(w));
+		let p = &self.brood_params;
+		w.f32(p.egg_cost);
+		w.f32(p.lay_interval);
+		w.f32(p.lay_threshold);
+		w.f32(p.egg_time);
+		w.f32(p.larva_time);
+		w.f32(p.larva_upkeep);
+		w.usize(p.max_brood);
+		w.f32(self.queen_food);
+	}
+	pub fn read(r : &mut ByteReader) -> io::Result<Self> {
+		Ok(Hive {
+			life: r.f32()?,
+			player_gave: r.bool()?,
+			player_died: r.bool()?,
+			caste_weights: [r.f32()?, r.f32()?, r.f32()?],
+			brood: r.seq(Brood::read)?,
+			brood_params: BroodParams {
+				egg_cost: r.f32()?,
+				lay_interval: r.f32()?,
+				lay_threshold: r.f32()?,
+				egg_time: r.f32()?,
+				larva_time: r.f32()?,
+				larva_upkeep: r.f32()?,
+				max_brood: r.usize()?,
+			},
+			queen_food: r.f32()?,
+		})
+	}
 }
@@ -12,24 +12,49 @@ mod game_objects;
 mod hive;
 mod nest;
 use nest::Nest;
+use world::TrailBackend;
 use prelude::*;
 mod gameplay;
 use gameplay::*;
 mod gobj;
 mod ants;
+mod predators;
 mod markers;
+mod pheromones;
 mod food;
+mod collision;
+mod pathfinding;
 mod world;
+mod simulation;
 
 #[macroquad::main(window_conf())]
 async fn main() {
 	let assets = Assets::load().await;
+	// --replay <file> plays a recorded session back, --record <file> records this one
+	let replay = arg_value("--replay").map(|p| Replay::load(&p).expect("Failed to load replay"));
+	// a replay runs on whatever it was recorded with, whatever the flags say now
+	let (seed, trails) = match &replay {
+		Some(r) => {
+			if arg_value("--trails").is_some() && TrailBackend::from_args() != r.trails {
+				println!("ignoring --trails, the replay was recorded with {:?}", r.trails);
+			}
+			(r.seed, r.trails)
+		},
+		None => (seed_from_args(), TrailBackend::from_args()),
+	};
+	let input_source = match (replay, arg_value("--record")) {
+		(Some(r), _) => InputSource::Playback(r, 0),
+		(None, Some(path)) => InputSource::Recording(
+			ReplayRecorder::create(&path, seed, trails).expect("Failed to create replay file")),
+		(None, None) => InputSource::Live,
+	};
 	let hive = Rc::new(RefCell::new(Hive::new()));
 	let mut ctx = Context::new(
 		vec![
-			Box::new(Nest::new(hive.clone())),
-			Box::new(Gameplay::new(hive.clone())),
-		]
+			Box::new(Nest::new(hive.clone(), seed, trails)),
+			Box::new(Gameplay::new(hive.clone(), seed, trails)),
+		],
+		input_source
 	);
 	ctx.init(assets);
 
@@ -39,12 +64,6 @@ async fn main() {
 	camera.render_target = Some(rt);
 
 	loop {
-		//if is_key_down(KeyCode::LeftAlt) {
-		//	for _ in 0..100 {
-		//		ctx.update();
-		//	}
-		//}
-
 		ctx.update();
 
 		//if !is_key_down(KeyCode::LeftControl) {
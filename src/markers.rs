@@ -1,14 +1,25 @@
-use std::{collections::HashMap, cell::RefCell, rc::Rc};
-use macroquad::{prelude::{Vec2, RED, vec2, WHITE}, shapes::{draw_rectangle_lines, draw_circle_lines}, texture::{draw_texture_ex, DrawTextureParams}};
+use std::{cell::RefCell, rc::Rc, io};
+use macroquad::{prelude::{Vec2, RED, vec2, WHITE, MAGENTA}, shapes::{draw_rectangle_lines, draw_circle_lines}, texture::{draw_texture_ex, DrawTextureParams}};
 
-use crate::{game_objects::RenderData, gobj::{FOOD_MARKER_LIFE, HOME_MARKER_LIFE, ANT_RAD, Gobj, ParticleStyle}, resources::*};
+use crate::{game_objects::RenderData, pheromones::Pheromone, gobj::{FOOD_MARKER_LIFE, HOME_MARKER_LIFE, ALARM_MARKER_LIFE, ANT_RAD, ANT_MARKER_DIST, Gobj, ParticleStyle}, resources::*, prelude::{Rng, ByteWriter, ByteReader, invalid_data, SpatialGrid}};
 
-pub const MAX_HOME_MARKERS_PER_CELL : usize = 14;
+/// Strength of a freshly dropped marker.
+pub const MARKER_DEPOSIT : f32 = 1.;
+/// Alarms are dropped rarely, so each one is a lot stronger.
+pub const ALARM_DEPOSIT : f32 = MARKER_DEPOSIT*5.;
+/// Markers fade exponentially and vanish below this; about e^-3, so a lone
+/// marker lasts roughly its `*_MARKER_LIFE`.
+pub const MARKER_MIN_STRENGTH : f32 = 0.05;
+pub const MAX_MARKER_STRENGTH : f32 = 20.;
+/// A new marker this close to one of the same kind reinforces it instead.
+pub const MARKER_MERGE_RADIUS : f32 = ANT_MARKER_DIST*0.5;
 pub const MARKER_RENDER_RAD : f32 = ANT_RAD*1.0;
+pub const MARKER_SEARCH_RANGE : f32 = 55.;
 #[derive(PartialEq, Debug, Clone)]
 pub enum Marker {
 	Home(Vec2, f32),
-	Food(Vec2, f32)
+	Food(Vec2, f32),
+	Alarm(Vec2, f32),
 }
 impl Marker {
 	pub fn pos(&self) -> &Vec2 {
@@ -16,54 +27,82 @@ impl Marker {
 		match self {
 			Home(p, _) => p,
 			Food(p, _) => p,
+			Alarm(p, _) => p,
 		}
 	}
-	fn life(&self) -> &f32 {
+	pub fn pheromone(&self) -> Pheromone {
+		match self {
+			Marker::Home(..) => Pheromone::Home,
+			Marker::Food(..) => Pheromone::Food,
+			Marker::Alarm(..) => Pheromone::Alarm,
+		}
+	}
+	pub fn strength(&self) -> &f32 {
 		use Marker::*;
 		match self {
 			Home(_, l) => l,
 			Food(_, l) => l,
+			Alarm(_, l) => l,
 		}
 	}
-	fn life_mut(&mut self) -> &mut f32 {
+	fn strength_mut(&mut self) -> &mut f32 {
 		use Marker::*;
 		match self {
 			Home(_, l) => l,
 			Food(_, l) => l,
+			Alarm(_, l) => l,
+		}
+	}
+	pub fn write(&self, w : &mut ByteWriter) {
+		w.u8(match self {
+			Marker::Home(..) => 0,
+			Marker::Food(..) => 1,
+			Marker::Alarm(..) => 2,
+		});
+		w.vec2(self.pos());
+		w.f32(*self.strength());
+	}
+	pub fn read(r : &mut ByteReader) -> io::Result<Self> {
+		let tag = r.u8()?;
+		let (pos, strength) = (r.vec2()?, r.f32()?);
+		match tag {
+			0 => Ok(Marker::Home(pos, strength)),
+			1 => Ok(Marker::Food(pos, strength)),
+			2 => Ok(Marker::Alarm(pos, strength)),
+			t => Err(invalid_data(&format!("bad Marker tag {t}"))),
 		}
 	}
 }
+/// Fresh markers render at full size, reinforced ones grow slowly.
+fn marker_render_scale(m : &Marker) -> f32 {
+	m.strength().sqrt().min(2.)
+}
 #[derive(Debug, PartialEq)]
 pub struct MarkerWorld {
-	grid_size : f32,
-	markers : HashMap<(i32, i32), Vec<Marker>>,
+	markers : SpatialGrid<Marker>,
 }
 impl MarkerWorld {
 	pub fn new(grid_size : f32) -> Self {
 		MarkerWorld {
-			grid_size,
-			markers: HashMap::new()
+			markers: SpatialGrid::new(grid_size),
 		}
 	}
-	pub fn pos_to_key(&self, pos : &Vec2) -> (i32, i32) {
-		let key = *pos / self.grid_size;
-		(key.x.floor() as i32, key.y.floor() as i32)
-	}
-	pub fn create_marker(&mut self, m : Marker, spawn_queue : Rc<RefCell<Vec<Gobj>>>) {
-		let key = self.pos_to_key(&m.pos());
-		if !self.markers.contains_key(&key) {
-			self.markers.insert(key, Vec::new());
-		}
-		match m {
-			Marker::Home(..) =>
-				if self.markers.get(&key)
-					.unwrap()
-					.iter()
-					.filter(|m| match m { Marker::Home(..) => true, _ => false })
-					.count() > MAX_HOME_MARKERS_PER_CELL {
-						return;
-					}
-			_ => ()
+	pub fn create_marker(&mut self, m : Marker, spawn_queue : Rc<RefCell<Vec<Gobj>>>, rng : &mut Rng) {
+		let same_kind = |o : &Marker| std::mem::discriminant(o) == std::mem::discriminant(&m);
+		let closest = self.markers
+			.query_radius(m.pos(), MARKER_MERGE_RADIUS)
+			.filter(|(_, o)| same_kind(o))
+			.min_by(|a, b| a.0.distance(*m.pos()).total_cmp(&b.0.distance(*m.pos())))
+			.map(|(p, _)| *p);
+		if let Some(p) = closest {
+			let key = self.markers.key(&p);
+			let (_, o) = self.markers.cell_mut(&key)
+				.iter_mut()
+				.find(|(op, o)| *op == p && same_kind(o))
+				.unwrap();
+			let s = o.strength_mut();
+			*s = (*s + m.strength()).min(MAX_MARKER_STRENGTH);
+			return;
 		}
 		spawn_queue.borrow_mut().push(match m.clone() {
 			Marker::Home(..) => Gobj::new_particles(
@@ -72,7 +111,8 @@ impl MarkerWorld {
 				HOME_MARKER_LIFE,
 				MARKER_RENDER_RAD*0.5,
 				COL_MARKER_HOME,
-				ParticleStyle::Orbit(0.5, 0.03)
+				ParticleStyle::Orbit(0.5, 0.03),
+				rng
 			),
 			Marker::Food(..) => Gobj::new_particles(
 				m.pos(),
@@ -80,68 +120,85 @@ impl MarkerWorld {
 				FOOD_MARKER_LIFE,
 				MARKER_RENDER_RAD*0.5,
 				COL_MARKER_FOOD,
-				ParticleStyle::Orbit(0.9, 0.06)
+				ParticleStyle::Orbit(0.9, 0.06),
+				rng
+			),
+			// a quick burst rather than a lingering cloud, it's meant to startle
+			Marker::Alarm(..) => Gobj::new_particles(
+				m.pos(),
+				8,
+				ALARM_MARKER_LIFE*0.2,
+				MARKER_RENDER_RAD,
+				COL_MARKER_ALARM,
+				ParticleStyle::Explosive(30., 2.),
+				rng
 			),
 		});
 
-		self.markers
-			.get_mut(&key)
-			.unwrap()
-			.push(m);
+		self.markers.insert(*m.pos(), m);
 	}
+	/// Markers within `MARKER_SEARCH_RANGE` of `pos+heading`.
 	pub fn local_markers(&self,
 		pos : &Vec2,
 		heading : &Vec2,
 		condition : &dyn Fn(&Marker) -> bool) -> Vec<Marker> {
-		let p = *pos+*heading;
-		let k = self.pos_to_key(&p);
+		self.markers
+			.query_radius(&(*pos+*heading), MARKER_SEARCH_RANGE)
+			.map(|(_, m)| m)
+			.filter(|m| condition(m))
+			.cloned()
+			.collect()
+	}
 
-		let mut out = Vec::new();
-		// every day, I am reminded of my severe mental disability
-		// (the range wasn't inclusive)
-		for x in (k.0-1)..=(k.0+1) {
-			for y in (k.1-1)..=(k.1+1) {
-				if !self.markers.contains_key(&(x, y)) {
-					continue;
-				}
-				for m in &self.markers[&(x, y)] {
-					if !condition(m) { continue; }
-					out.push(m.clone());
-				}
-			}
+	/// Total strength of `p` markers within `radius` of `pos`.
+	pub fn strength_near(&self, p : Pheromone, pos : &Vec2, radius : f32) -> f32 {
+		self.markers
+			.query_radius(pos, radius)
+			.filter(|(_, m)| m.pheromone() == p)
+			.map(|(_, m)| m.strength())
+			.sum()
+	}
+
+	pub fn write_state(&self, w : &mut ByteWriter) {
+		w.seq(&self.markers.cells(), |w, (k, v)| {
+			w.i32(k.0);
+			w.i32(k.1);
+			w.seq(v, |w, (_, m)| m.write(w));
+		});
+	}
+	pub fn read_state(&mut self, r : &mut ByteReader) -> io::Result<()> {
+		let cells = r.seq(|r| {
+			r.i32()?;
+			r.i32()?;
+			r.seq(Marker::read)
+		})?;
+		self.markers.clear();
+		for m in cells.into_iter().flatten() {
+			self.markers.insert(*m.pos(), m);
 		}
-		out
+		Ok(())
 	}
 
 	pub fn update(&mut self, d : f32) {
-		self.markers.retain(|_, v| {
-			let mut i = 0;
-			while i < v.len() {
-				*v[i].life_mut() -= d;
-				if *v[i].life() < 0.0 {
-					v.remove(i);
-				} else { i += 1 }
-			}
-			i != 0
+		self.markers.retain(|_, m| {
+			// proportional decay, so strong trails lose more per second but last longer
+			let keep = (-m.pheromone().evaporation()*d).exp();
+			*m.strength_mut() *= keep;
+			*m.strength() >= MARKER_MIN_STRENGTH
 		});
 	}
 	#[allow(dead_code)]
 	pub fn render_debug(&self, rd : &RenderData) {
-		for (k, v) in &self.markers {
-			let pos = vec2(
-				k.0 as f32 * self.grid_size,
-				k.1 as f32 * self.grid_size
-			);
-			let pos = pos - rd.camera_offset();
-			draw_rectangle_lines(pos.x, pos.y, self.grid_size, self.grid_size, 2., RED);
-			for p in v {
-				let a = match p {
-					Marker::Home(_, l) => l/HOME_MARKER_LIFE,
-					Marker::Food(_, l) => l/FOOD_MARKER_LIFE,
-				};
+		let grid_size = self.markers.cell_size();
+		for (k, v) in self.markers.cells() {
+			let pos = self.markers.cell_pos(k) - rd.camera_offset();
+			draw_rectangle_lines(pos.x, pos.y, grid_size, grid_size, 2., RED);
+			for (_, p) in v {
+				let a = marker_render_scale(p);
 				let col = match p {
 					Marker::Home(..) => WHITE,
 					Marker::Food(..) => RED,
+					Marker::Alarm(..) => MAGENTA,
 				};
 				let pos = *p.pos() - rd.camera_offset();
 				draw_circle_lines(pos.x, pos.y, a*2., 1.4, col);
@@ -150,16 +207,10 @@ impl MarkerWorld {
 	}
 	#[allow(unreachable_code)]
 	pub fn render(&self, rd : &RenderData) {
-		for (_, v) in &self.markers {
-			for p in v {
-				let a = match p {
-					Marker::Home(_, l) => l/HOME_MARKER_LIFE,
-					Marker::Food(_, l) => l/FOOD_MARKER_LIFE,
-				};
-				let col = match p {
-					Marker::Home(..) => COL_MARKER_HOME,
-					Marker::Food(..) => COL_MARKER_FOOD,
-				};
+		for (_, v) in self.markers.cells() {
+			for (_, p) in v {
+				let a = marker_render_scale(p);
+				let col = p.pheromone().colour();
 				let pos = rd.cast_pos(p.pos());
 				draw_texture_ex(
 					rd.assets.clone().unwrap().tex_fuzzy_0,
@@ -1,9 +1,9 @@
 use crate::Scene;
-use crate::gobj::PLAYER_RAD;
-use std::{rc::Rc, cell::RefCell};
+use crate::gobj::{PLAYER_RAD, QUEEN_FEED_RANGE};
+use std::{rc::Rc, cell::RefCell, io};
 use crate::*;
 
-use crate::{RenderData, game_objects::ObjectSet, gobj::Gobj, world::World, hive::Hive};
+use crate::{RenderData, game_objects::ObjectSet, gobj::Gobj, world::{World, TrailBackend}, hive::{Hive, Brood}};
 
 pub struct Nest {
 	rd : RenderData,
@@ -12,12 +12,12 @@ pub struct Nest {
 	world : Rc<RefCell<World>>,
 }
 impl Nest {
-	pub fn new(hive : Rc<RefCell<Hive>>) -> Self {
+	pub fn new(hive : Rc<RefCell<Hive>>, seed : u64, trails : TrailBackend) -> Self {
 		Nest {
 			rd: RenderData::new(),
 			objs: ObjectSet::new(),
 			sq: Rc::new(RefCell::new(Vec::new())),
-			world: Rc::new(RefCell::new(World::new(hive))),
+			world: Rc::new(RefCell::new(World::new(hive, seed, trails))),
 		}
 	}
 	fn render_map_vignette(&self, tl : &Vec2, map_dims : &Vec2, scale_factor : f32) {
@@ -34,8 +34,8 @@ impl Nest {
 			});
 	}
 	fn render_debug_map_col(&self) {
-		let css = self.world.borrow().collision_cell_size*self.rd.scale_unit(1.);
-		for (p, b) in self.world.borrow().collision_map.iter() {
+		let css = self.world.borrow().collision.cell_size()*self.rd.scale_unit(1.);
+		for (p, b) in self.world.borrow().collision.cells() {
 			let p = self.rd.cast_pos(
 				&(vec2(p.0 as f32, p.1 as f32)
 				* css
@@ -43,22 +43,52 @@ impl Nest {
 			draw_rectangle_lines(p.x, p.y,
 				css.x,
 				css.y, 1.0, RED);
-			if *b {
+			if b {
 				draw_rectangle(p.x, p.y,
 					css.x,
 					css.y, RED);
 			}
 		}
 	}
+	/// Eggs and larvae laid out in rows in the chamber, filling up as the hive lays.
+	fn render_brood(&self) {
+		let world = self.world.borrow();
+		let hive = world.hive.borrow();
+		for (i, b) in hive.brood.iter().enumerate() {
+			let cell = vec2((i%BROOD_COLS) as f32, (i/BROOD_COLS) as f32);
+			let p = self.rd.cast_pos(&(BROOD_CHAMBER + cell*BROOD_SPACING));
+			let (rad, col) = match b {
+				Brood::Egg(_) => (1.2, WHITE),
+				Brood::Larva(_) => (2., BEIGE),
+			};
+			draw_circle(p.x, p.y, self.rd.scale_unit(rad), col);
+		}
+	}
+	// the player is always object 0, see `player_pos`
+	fn spawn_player(&mut self) {
+		self.objs.create(Gobj::new_player(self.sq.clone(), self.world.clone(), &vec2(0., NEST_TOPLEFT.y+NEST_DIMS.y*0.6)));
+	}
+	fn spawn_colony(&mut self) {
+		self.objs.create(Gobj::new_queen(self.world.clone()));
+		for i in 0..NEST_NURSES {
+			let pos = NEST_STORE + vec2(i as f32*PLAYER_RAD*2., 0.);
+			self.objs.create(Gobj::new_nurse(self.world.clone(), &pos));
+		}
+	}
+	fn step(&mut self, d : f32, input : &FrameInput) {
+		self.objs.update(d, input);
+		self.sq.borrow_mut().clear();
+	}
 	pub fn player_pos(&self) -> Vec2 {
 		match self.objs.get_obj(0) {
-			Gobj::Player(_, _, pos, _, _, _) => *pos,
+			Gobj::Player(_, _, pos, ..) => *pos,
 			_ => panic!("player_id ({}) not pointing to a Player!", 0)
 		}
 	}
 	fn set_player_pos(&mut self, p : &Vec2) {
+		let p = self.world.borrow().free_pos(p);
 		match self.objs.get_obj_mut(0) {
-			Gobj::Player(_, _, pos, _, _, _) => *pos = *p,
+			Gobj::Player(_, _, pos, prev_pos, ..) => { *pos = p; *prev_pos = p; },
 			_ => panic!("player_id ({}) not pointing to a Player!", 0)
 		}
 	}
@@ -66,38 +96,74 @@ impl Nest {
 const NEST_SIZE : f32 = 500.;
 pub const NEST_DIMS : Vec2 = Vec2::new(NEST_SIZE, NEST_SIZE);
 pub const NEST_TOPLEFT : Vec2 = Vec2::new(-NEST_DIMS.x/2., -NEST_DIMS.y/2.);
+/// Where the queen sits, on the right of the main chamber.
+pub const QUEEN_POS : Vec2 = Vec2::new(45., -25.);
+/// Where nurses pick up food for the queen.
+pub const NEST_STORE : Vec2 = Vec2::new(0., 40.);
+/// How many nurses look after the queen.
+const NEST_NURSES : usize = 3;
+/// Food per second the player hands the queen while standing next to her.
+const PLAYER_FEED_RATE : f32 = 10.;
+/// Where the player wakes up after dying on the surface.
+const NEST_RESPAWN : Vec2 = NEST_STORE;
+/// Top left of where the brood is drawn, in the upper left of the main chamber.
+const BROOD_CHAMBER : Vec2 = Vec2::new(-50., -40.);
+const BROOD_COLS : usize = 6;
+const BROOD_SPACING : f32 = 6.;
 impl Scene for Nest {
 	fn init(&mut self, a : &Assets) {
 		self.rd.assets = Some(a.clone());
 		self.rd.zoom = 0.8;
 
 		self.world.borrow_mut().map_tl = NEST_TOPLEFT;
-		self.world.borrow_mut().init(&a.tex_nest_col, &NEST_DIMS);
+		self.world.borrow_mut().init(&a.tex_nest_col.get_texture_data(), &NEST_DIMS);
 
-		self.objs.create(Gobj::new_player(self.sq.clone(), self.world.clone(), &vec2(0., NEST_TOPLEFT.y+NEST_DIMS.y*0.6)));
+		self.spawn_player();
+		self.spawn_colony();
+	}
+	fn restart(&mut self) {
+		self.objs = ObjectSet::new();
+		self.sq.borrow_mut().clear();
+		self.world.borrow_mut().reset();
+		self.spawn_player();
+		self.spawn_colony();
 	}
 	fn load(&mut self) {
-		self.set_player_pos(&vec2(0., NEST_TOPLEFT.y+NEST_DIMS.y*0.7));
+		let died = self.world.borrow().hive.borrow_mut().did_player_die();
+		let at = if died { NEST_RESPAWN } else { vec2(0., NEST_TOPLEFT.y+NEST_DIMS.y*0.7) };
+		self.set_player_pos(&at);
 		self.objs.create(Gobj::Fader(1.0));
 	}
-    fn update(&mut self, q : &mut SignalQueue) {
-		self.objs.update();
-		self.sq.borrow_mut().clear();
+    fn update(&mut self, q : &mut SignalQueue, d : f32, input : &FrameInput) {
+		self.step(d, input);
 
+		if self.player_pos().distance(QUEEN_POS) < QUEEN_FEED_RANGE {
+			self.world.borrow().hive.borrow_mut().feed_queen(PLAYER_FEED_RATE*d);
+		}
+		// the surface is where the game over screen lives
+		if self.world.borrow().hive.borrow().lost() {
+			q.send(Signal::SetScene(1));
+		}
 		if self.player_pos().y > NEST_TOPLEFT.y + NEST_DIMS.y - PLAYER_RAD*20. {
 			q.send(Signal::SetScene(1));
 		}
     }
 
-    fn render(&mut self, _q : &mut SignalQueue) {
+	fn background_update(&mut self, d : f32) {
+		self.step(d, &FrameInput::default());
+	}
+
+    fn render(&mut self, _q : &mut SignalQueue, alpha : f32) {
 		let d = get_frame_time().min(0.2).max(0.001);
+		self.rd.alpha = alpha;
+		let player_pos = self.objs.get_obj(0).render_pos(&self.rd).unwrap();
 		self.rd.camera_pos = lerp(
 			self.rd.camera_pos,
-			self.player_pos()*0.6,
+			player_pos*0.6,
 			d*3.);
 		self.rd.zoom = lerp(
 			self.rd.zoom, 
-			if self.player_pos().y < PLAYER_RAD*18. {
+			if player_pos.y < PLAYER_RAD*18. {
 				0.3
 			} else { 1.0 },
 			d);
@@ -117,6 +183,7 @@ impl Scene for Nest {
 				dest_size: Some(NEST_DIMS*self.rd.scale_unit(1.)),
 				..DrawTextureParams::default()
 			});
+		self.render_brood();
 		self.objs.render(&self.rd);
 
 		self.render_map_vignette(&NEST_TOPLEFT, &NEST_DIMS, 1.9);
@@ -128,5 +195,15 @@ impl Scene for Nest {
     }
 
     fn unload(&mut self) {}
+
+	fn write_state(&self, w : &mut ByteWriter) {
+		self.objs.write_state(w, |w, o| o.write(w));
+		self.world.borrow().write_state(w);
+	}
+	fn read_state(&mut self, r : &mut ByteReader) -> io::Result<()> {
+		let (sq, world) = (self.sq.clone(), self.world.clone());
+		self.objs.read_state(r, |r| Gobj::read(r, sq.clone(), world.clone()))?;
+		self.world.borrow_mut().read_state(r)
+	}
 }
 
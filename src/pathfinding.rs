@@ -0,0 +1,231 @@
+use std::{collections::BinaryHeap, cmp::Ordering};
+use macroquad::{prelude::{Vec2, Color}, shapes::draw_rectangle};
+
+use crate::{collision::CollisionMap, game_objects::RenderData};
+
+type Cell = (i32, i32);
+const NEIGHBOURS : [Cell; 8] = [
+	(1, 0), (-1, 0), (0, 1), (0, -1),
+	(1, 1), (1, -1), (-1, 1), (-1, -1),
+];
+/// Free cells next to `c` with the cost of stepping there. Diagonal steps
+/// aren't allowed to cut the corner of a wall.
+fn neighbours(map : &CollisionMap, c : Cell) -> impl Iterator<Item = (Cell, f32)> + '_ {
+	let cs = map.cell_size();
+	NEIGHBOURS.iter().filter_map(move |(dx, dy)| {
+		let n = (c.0 + dx, c.1 + dy);
+		if map.is_solid(n.0, n.1) { return None }
+		if *dx != 0 && *dy != 0 && (map.is_solid(c.0 + dx, c.1) || map.is_solid(c.0, c.1 + dy)) {
+			return None;
+		}
+		Some((n, (Vec2::new(*dx as f32, *dy as f32)*cs).length()))
+	})
+}
+
+/// Min-heap entry, ordered by cost only.
+struct Open(f32, Cell);
+impl PartialEq for Open {
+	fn eq(&self, other : &Self) -> bool { self.0 == other.0 }
+}
+impl Eq for Open {}
+impl PartialOrd for Open {
+	fn partial_cmp(&self, other : &Self) -> Option<Ordering> { Some(self.cmp(other)) }
+}
+impl Ord for Open {
+	fn cmp(&self, other : &Self) -> Ordering {
+		other.0.total_cmp(&self.0).then_with(|| other.1.cmp(&self.1))
+	}
+}
+
+/// A* over the collision grid. Returns the waypoints after `from`, ending at `to`.
+pub fn find_path(map : &CollisionMap, from : &Vec2, to : &Vec2) -> Option<Vec<Vec2>> {
+	let (start, goal) = (map.cell_of(from), map.cell_of(to));
+	if map.is_solid(start.0, start.1) || map.is_solid(goal.0, goal.1) {
+		return None;
+	}
+	let (w, h) = map.dims();
+	let mut g = vec![f32::INFINITY; (w*h) as usize];
+	let mut came_from : Vec<Option<Cell>> = vec![None; (w*h) as usize];
+	let goal_centre = map.cell_centre(goal.0, goal.1);
+	let heuristic = |c : Cell| map.cell_centre(c.0, c.1).distance(goal_centre);
+
+	let mut open = BinaryHeap::new();
+	g[map.index(start.0, start.1)?] = 0.;
+	open.push(Open(heuristic(start), start));
+	while let Some(Open(f, c)) = open.pop() {
+		// a cheaper way here was found after this entry was pushed
+		if f > g[map.index(c.0, c.1)?] + heuristic(c) { continue }
+		if c == goal {
+			let mut path = vec![*to];
+			let mut c = came_from[map.index(c.0, c.1)?];
+			while let Some(p) = c {
+				if p == start { break }
+				path.push(map.cell_centre(p.0, p.1));
+				c = came_from[map.index(p.0, p.1)?];
+			}
+			path.reverse();
+			return Some(path);
+		}
+		let gc = g[map.index(c.0, c.1)?];
+		for (n, cost) in neighbours(map, c) {
+			let i = map.index(n.0, n.1)?;
+			if gc + cost < g[i] {
+				g[i] = gc + cost;
+				came_from[i] = Some(c);
+				open.push(Open(g[i] + heuristic(n), n));
+			}
+		}
+	}
+	None
+}
+
+/// Walking distance from every free cell to one goal, so any number of agents
+/// can head for it without pathfinding each.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FlowField {
+	goal : Vec2,
+	dist : Vec<f32>,
+}
+impl FlowField {
+	pub fn empty() -> Self {
+		FlowField { goal: Vec2::ZERO, dist: Vec::new() }
+	}
+	pub fn new(map : &CollisionMap, goal : &Vec2) -> Self {
+		let (w, h) = map.dims();
+		let mut dist = vec![f32::INFINITY; (w*h) as usize];
+		let start = map.cell_of(goal);
+		let mut open = BinaryHeap::new();
+		if let Some(i) = map.index(start.0, start.1) {
+			dist[i] = 0.;
+			open.push(Open(0., start));
+		}
+		while let Some(Open(d, c)) = open.pop() {
+			if d > dist[map.index(c.0, c.1).unwrap()] { continue }
+			for (n, cost) in neighbours(map, c) {
+				let i = map.index(n.0, n.1).unwrap();
+				if d + cost < dist[i] {
+					dist[i] = d + cost;
+					open.push(Open(dist[i], n));
+				}
+			}
+		}
+		FlowField { goal: *goal, dist }
+	}
+	pub fn distance(&self, map : &CollisionMap, pos : &Vec2) -> Option<f32> {
+		let c = map.cell_of(pos);
+		map.index(c.0, c.1)
+			.and_then(|i| self.dist.get(i))
+			.copied()
+			.filter(|d| d.is_finite())
+	}
+	/// Which way to walk from `pos` to get closer to the goal.
+	pub fn direction(&self, map : &CollisionMap, pos : &Vec2) -> Option<Vec2> {
+		let c = map.cell_of(pos);
+		let here = self.distance(map, pos)?;
+		if here == 0. {
+			return Some((self.goal - *pos).normalize_or_zero());
+		}
+		neighbours(map, c)
+			.filter_map(|(n, _)| Some((n, self.dist[map.index(n.0, n.1)?])))
+			.filter(|(_, d)| *d < here)
+			.min_by(|a, b| a.1.total_cmp(&b.1))
+			.map(|(n, _)| (map.cell_centre(n.0, n.1) - *pos).normalize_or_zero())
+	}
+	pub fn render(&self, map : &CollisionMap, rd : &RenderData) {
+		let max = self.dist.iter().copied().filter(|d| d.is_finite()).fold(1., f32::max);
+		let size = map.cell_size()*rd.scale_unit(1.);
+		for ((x, y), solid) in map.cells() {
+			if solid { continue }
+			let Some(d) = map.index(x, y).and_then(|i| self.dist.get(i)) else { continue };
+			if !d.is_finite() { continue }
+			let t = d/max;
+			let p = rd.cast_pos(&(map.cell_centre(x, y) - map.cell_size()/2.));
+			draw_rectangle(p.x, p.y, size.x, size.y, Color::new(t, 1. - t, 0.2, 0.3));
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::collision::tests::from_rows;
+	use macroquad::prelude::vec2;
+
+	fn wall() -> CollisionMap {
+		from_rows(&[
+			"..........",
+			"....#.....",
+			"....#.....",
+			"....#.....",
+			"....#.....",
+			"..........",
+		])
+	}
+	/// Only closed off by corners, which diagonal steps can't cut.
+	fn pocket() -> CollisionMap {
+		from_rows(&[
+			"........",
+			"...##...",
+			"..#..#..",
+			"..#..#..",
+			"...##...",
+			"........",
+		])
+	}
+	fn length(from : &Vec2, path : &[Vec2]) -> f32 {
+		std::iter::once(from).chain(path).zip(path).map(|(a, b)| a.distance(*b)).sum()
+	}
+
+	#[test]
+	fn path_around_a_wall() {
+		let map = wall();
+		let (from, to) = (vec2(1.5, 2.5), vec2(7.5, 2.5));
+		let path = find_path(&map, &from, &to).unwrap();
+		assert_eq!(path.last(), Some(&to));
+		let mut at = from;
+		for p in path.iter() {
+			assert!(map.segment_clear(&at, p), "{at} to {p} goes through a wall");
+			at = *p;
+		}
+		// over the top is the short way round
+		assert!(path.iter().any(|p| p.y < 1.));
+		let len = length(&from, &path);
+		assert!(len > from.distance(to) && len < 9., "{len}");
+	}
+	#[test]
+	fn unreachable_goal() {
+		let map = pocket();
+		assert_eq!(find_path(&map, &vec2(0.5, 0.5), &vec2(3.5, 2.5)), None);
+		assert_eq!(find_path(&map, &vec2(4.5, 3.5), &vec2(7.5, 5.5)), None);
+		// into a wall, or off the map
+		assert_eq!(find_path(&map, &vec2(0.5, 0.5), &vec2(2.5, 2.5)), None);
+		assert_eq!(find_path(&map, &vec2(0.5, 0.5), &vec2(-3., 0.5)), None);
+		// within the pocket, or around the outside of it, is fine
+		assert_eq!(find_path(&map, &vec2(3.5, 2.5), &vec2(4.5, 3.5)), Some(vec![vec2(4.5, 3.5)]));
+		assert!(find_path(&map, &vec2(0.5, 0.5), &vec2(7.5, 5.5)).is_some());
+	}
+	#[test]
+	fn flow_field_leads_around_a_wall() {
+		let map = wall();
+		let goal = vec2(7.5, 2.5);
+		let flow = FlowField::new(&map, &goal);
+		assert_eq!(flow.distance(&map, &goal), Some(0.));
+		let mut p = vec2(1.5, 2.5);
+		for _ in 0..100 {
+			if p.distance(goal) < 0.1 { break }
+			let dir = flow.direction(&map, &p).unwrap();
+			let next = p + dir*0.1;
+			assert!(!map.is_collision(&next), "walked into the wall at {next}");
+			p = next;
+		}
+		assert!(p.distance(goal) < 0.1, "stopped at {p}");
+	}
+	#[test]
+	fn flow_field_unreachable() {
+		let map = pocket();
+		let flow = FlowField::new(&map, &vec2(0.5, 0.5));
+		assert_eq!(flow.distance(&map, &vec2(3.5, 2.5)), None);
+		assert_eq!(flow.direction(&map, &vec2(3.5, 2.5)), None);
+		assert!(flow.distance(&map, &vec2(7.5, 5.5)).is_some());
+	}
+}
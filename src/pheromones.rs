@@ -0,0 +1,150 @@
+use std::io;
+use macroquad::{prelude::{Vec2, vec2, Color}, shapes::draw_rectangle};
+
+use crate::{game_objects::RenderData, gobj::{HOME_MARKER_LIFE, FOOD_MARKER_LIFE, ALARM_MARKER_LIFE}, resources::*, prelude::{ByteWriter, ByteReader, invalid_data}};
+
+pub const FIELD_CELL_SIZE : f32 = 10.;
+/// Per-step diffusion rate, explicit diffusion is only stable up to 0.25.
+pub const FIELD_DIFFUSION : f32 = 0.05;
+/// Cells below this aren't drawn.
+pub const FIELD_THRESHOLD : f32 = 0.02;
+const FIELD_RENDER_MAX : f32 = 3.;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Pheromone {
+	Home,
+	Food,
+	Alarm,
+}
+pub const PHEROMONES : [Pheromone; 3] = [Pheromone::Home, Pheromone::Food, Pheromone::Alarm];
+impl Pheromone {
+	fn index(self) -> usize { self as usize }
+	/// Fraction lost per second; roughly matches how long the equivalent marker lives.
+	pub fn evaporation(self) -> f32 {
+		match self {
+			Pheromone::Home => 3./HOME_MARKER_LIFE,
+			Pheromone::Food => 3./FOOD_MARKER_LIFE,
+			Pheromone::Alarm => 3./ALARM_MARKER_LIFE,
+		}
+	}
+	pub fn colour(self) -> Color {
+		match self {
+			Pheromone::Home => COL_MARKER_HOME,
+			Pheromone::Food => COL_MARKER_FOOD,
+			Pheromone::Alarm => COL_MARKER_ALARM,
+		}
+	}
+}
+
+/// One scalar grid per `Pheromone`, covering the map. The continuous
+/// alternative to `MarkerWorld`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PheromoneField {
+	tl : Vec2,
+	w : usize,
+	h : usize,
+	pub diffusion : f32,
+	channels : Vec<Vec<f32>>,
+	scratch : Vec<f32>,
+}
+impl PheromoneField {
+	pub fn new(tl : Vec2, dims : &Vec2) -> Self {
+		let w = (dims.x/FIELD_CELL_SIZE).ceil() as usize;
+		let h = (dims.y/FIELD_CELL_SIZE).ceil() as usize;
+		PheromoneField {
+			tl,
+			w,
+			h,
+			diffusion: FIELD_DIFFUSION,
+			channels: vec![vec![0.; w*h]; PHEROMONES.len()],
+			scratch: vec![0.; w*h],
+		}
+	}
+	pub fn clear(&mut self) {
+		for c in self.channels.iter_mut() {
+			c.fill(0.);
+		}
+	}
+	fn cell(&self, x : i32, y : i32) -> usize {
+		let x = x.clamp(0, self.w as i32 - 1) as usize;
+		let y = y.clamp(0, self.h as i32 - 1) as usize;
+		y*self.w + x
+	}
+	pub fn deposit(&mut self, p : Pheromone, pos : &Vec2, amount : f32) {
+		if self.w == 0 || self.h == 0 { return }
+		let g = (*pos - self.tl)/FIELD_CELL_SIZE;
+		let i = self.cell(g.x.floor() as i32, g.y.floor() as i32);
+		self.channels[p.index()][i] += amount;
+	}
+	/// Bilinear, with cell values sitting at cell centres.
+	pub fn sample(&self, p : Pheromone, pos : &Vec2) -> f32 {
+		if self.w == 0 || self.h == 0 { return 0. }
+		let g = (*pos - self.tl)/FIELD_CELL_SIZE - Vec2::splat(0.5);
+		let (x, y) = (g.x.floor() as i32, g.y.floor() as i32);
+		let (fx, fy) = (g.x - x as f32, g.y - y as f32);
+		let c = &self.channels[p.index()];
+		let top = c[self.cell(x, y)]*(1.-fx) + c[self.cell(x+1, y)]*fx;
+		let bottom = c[self.cell(x, y+1)]*(1.-fx) + c[self.cell(x+1, y+1)]*fx;
+		top*(1.-fy) + bottom*fy
+	}
+
+	pub fn update(&mut self, d : f32) {
+		for p in PHEROMONES {
+			let keep = (-p.evaporation()*d).exp();
+			let c = &mut self.channels[p.index()];
+			if self.diffusion > 0. {
+				for y in 0..self.h as i32 {
+					for x in 0..self.w as i32 {
+						let i = (y as usize)*self.w + x as usize;
+						let n = [(x-1, y), (x+1, y), (x, y-1), (x, y+1)]
+							.iter()
+							.map(|(nx, ny)| c[(*ny).clamp(0, self.h as i32 - 1) as usize*self.w
+								+ (*nx).clamp(0, self.w as i32 - 1) as usize])
+							.sum::<f32>();
+						self.scratch[i] = c[i] + self.diffusion*(n - 4.*c[i]);
+					}
+				}
+				std::mem::swap(c, &mut self.scratch);
+			}
+			for v in c.iter_mut() {
+				*v *= keep;
+			}
+		}
+	}
+
+	pub fn write_state(&self, w : &mut ByteWriter) {
+		w.usize(self.w);
+		w.usize(self.h);
+		for c in self.channels.iter() {
+			w.seq(c, |w, v| w.f32(*v));
+		}
+	}
+	pub fn read_state(&mut self, r : &mut ByteReader) -> io::Result<()> {
+		let (w, h) = (r.usize()?, r.usize()?);
+		if (w, h) != (self.w, self.h) {
+			return Err(invalid_data(&format!("pheromone field is {w}x{h}, expected {}x{}", self.w, self.h)));
+		}
+		for i in 0..self.channels.len() {
+			let c = r.seq(|r| r.f32())?;
+			if c.len() != w*h {
+				return Err(invalid_data("pheromone channel has the wrong size"));
+			}
+			self.channels[i] = c;
+		}
+		Ok(())
+	}
+
+	pub fn render(&self, rd : &RenderData) {
+		let size = rd.scale_unit(FIELD_CELL_SIZE);
+		for p in PHEROMONES {
+			let c = &self.channels[p.index()];
+			for (i, v) in c.iter().enumerate() {
+				if *v < FIELD_THRESHOLD { continue }
+				let pos = rd.cast_pos(&(self.tl + vec2((i%self.w) as f32, (i/self.w) as f32)*FIELD_CELL_SIZE));
+				let mut col = p.colour();
+				col.a *= (v/FIELD_RENDER_MAX).min(1.)*0.6;
+				draw_rectangle(pos.x, pos.y, size, size, col);
+			}
+		}
+	}
+}
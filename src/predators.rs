@@ -0,0 +1,119 @@
+use std::io;
+
+use macroquad::prelude::{Color, Vec2};
+use crate::{game_objects::GameObjectID, gobj::{ANT_RAD, ANT_SPEED}, markers::ALARM_DEPOSIT, prelude::{ByteWriter, ByteReader, invalid_data}};
+
+/// Seconds a predator stares at what it spotted before going for it.
+pub const PREDATOR_NOTICE_TIME : f32 = 0.8;
+/// How long a chase lasts before it gives up.
+pub const PREDATOR_CHASE_TIME : f32 = 6.;
+/// How long it spends walking away before it's gone for good.
+pub const PREDATOR_RETREAT_TIME : f32 = 6.;
+/// Alarm this strong around a predator scares it off.
+pub const PREDATOR_SCARE_SMELL : f32 = ALARM_DEPOSIT*0.8;
+
+/// What a predator is after.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Prey {
+	Ant(GameObjectID),
+	Player,
+}
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PredatorState {
+	/// Walking to a spot on the map.
+	Patrol(Vec2),
+	/// Spotted something, winding up before the chase. The f32 is time left.
+	Notice(Prey, f32),
+	Chase(Prey, f32),
+	/// Full or scared, leaving for the given number of seconds.
+	Retreat(f32),
+}
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PredatorParams {
+	pub speed : f32,
+	pub chase_speed : f32,
+	pub radius : f32,
+	pub notice_range : f32,
+	/// Ants it eats before it's had enough and leaves.
+	pub meals : usize,
+	/// Health the player loses when caught.
+	pub bite : f32,
+	pub tint : Color,
+}
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PredatorKind {
+	Spider,
+	Beetle,
+}
+pub const PREDATOR_KINDS : [PredatorKind; 2] = [PredatorKind::Spider, PredatorKind::Beetle];
+const SPIDER : PredatorParams = PredatorParams {
+	speed: ANT_SPEED*0.6,
+	chase_speed: ANT_SPEED*1.3,
+	radius: ANT_RAD*3.,
+	notice_range: ANT_RAD*20.,
+	meals: 3,
+	bite: 1.,
+	tint: Color::new(0.15, 0.1, 0.1, 1.),
+};
+const BEETLE : PredatorParams = PredatorParams {
+	speed: ANT_SPEED*0.4,
+	chase_speed: ANT_SPEED*1.1,
+	radius: ANT_RAD*4.,
+	notice_range: ANT_RAD*14.,
+	meals: 5,
+	bite: 1.5,
+	tint: Color::new(0.2, 0.25, 0.15, 1.),
+};
+impl PredatorKind {
+	pub fn params(self) -> &'static PredatorParams {
+		match self {
+			PredatorKind::Spider => &SPIDER,
+			PredatorKind::Beetle => &BEETLE,
+		}
+	}
+	pub fn write(&self, w : &mut ByteWriter) {
+		w.u8(*self as u8);
+	}
+	pub fn read(r : &mut ByteReader) -> io::Result<Self> {
+		match r.u8()? {
+			t if (t as usize) < PREDATOR_KINDS.len() => Ok(PREDATOR_KINDS[t as usize]),
+			t => Err(invalid_data(&format!("bad PredatorKind tag {t}"))),
+		}
+	}
+}
+impl Prey {
+	pub fn write(&self, w : &mut ByteWriter) {
+		match self {
+			Prey::Ant(id) => { w.u8(0); w.usize(*id); },
+			Prey::Player => w.u8(1),
+		}
+	}
+	pub fn read(r : &mut ByteReader) -> io::Result<Self> {
+		Ok(match r.u8()? {
+			0 => Prey::Ant(r.usize()?),
+			1 => Prey::Player,
+			t => return Err(invalid_data(&format!("bad Prey tag {t}"))),
+		})
+	}
+}
+impl PredatorState {
+	pub fn write(&self, w : &mut ByteWriter) {
+		use PredatorState::*;
+		match self {
+			Patrol(to) => { w.u8(0); w.vec2(to); },
+			Notice(prey, left) => { w.u8(1); prey.write(w); w.f32(*left); },
+			Chase(prey, left) => { w.u8(2); prey.write(w); w.f32(*left); },
+			Retreat(left) => { w.u8(3); w.f32(*left); },
+		}
+	}
+	pub fn read(r : &mut ByteReader) -> io::Result<Self> {
+		use PredatorState::*;
+		Ok(match r.u8()? {
+			0 => Patrol(r.vec2()?),
+			1 => Notice(Prey::read(r)?, r.f32()?),
+			2 => Chase(Prey::read(r)?, r.f32()?),
+			3 => Retreat(r.f32()?),
+			t => return Err(invalid_data(&format!("bad PredatorState tag {t}"))),
+		})
+	}
+}
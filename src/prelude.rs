@@ -6,6 +6,10 @@ pub use crate::{
 		rendering::*,
 		context::*,
 		input::*,
+		replay::*,
+		serial::*,
+		time::*,
+		spatial::*,
 	},
 	resources::*, 
 	config::*,
@@ -6,6 +6,7 @@ pub const FONT_FILE: &[u8] = include_bytes!("../res/m6x11.ttf");
 pub const COL_BG : Color = Color::new(0.01, 0.02, 0.04, 1.);
 pub const COL_MARKER_HOME : Color = Color::new(0.67, 0.64, 0.65, 1.);
 pub const COL_MARKER_FOOD : Color = Color::new(1.00, 0.51, 0.24, 1.);
+pub const COL_MARKER_ALARM : Color = Color::new(0.95, 0.15, 0.25, 1.);
 
 pub const PARTICLE_SIZE : f32 = 6.0;
 
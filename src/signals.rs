@@ -2,7 +2,8 @@ use std::collections::VecDeque;
 
 #[derive(Clone, Debug)]
 pub enum Signal {
-	SetScene(usize)
+	SetScene(usize),
+	Restart,
 }
 
 #[derive(Clone, Debug)]
@@ -0,0 +1,434 @@
+use std::cell::RefCell;
+use std::io;
+use std::rc::Rc;
+
+use macroquad::prelude::*;
+use crate::food::{Food, FOOD_SOURCE_MIN, FOOD_SOURCE_MAX};
+use crate::hive::{Hive, HIVE_MAX_LIFE};
+use crate::prelude::*;
+use crate::gobj::*;
+use crate::world::MAP_DIMS;
+use crate::world::{World, TrailBackend};
+use crate::predators::PREDATOR_KINDS;
+
+pub const FOOD_TIMER_LOW : f32 = 15.0;
+pub const FOOD_TIMER_HIGH : f32 = 36.0;
+pub const PRESIM_STEPS : usize = 200;
+/// Seconds between predators for a starving and a full hive; a thriving
+/// colony draws them in faster.
+pub const PREDATOR_TIMER_HIGH : f32 = 90.0;
+pub const PREDATOR_TIMER_LOW : f32 = 30.0;
+const MAX_PREDATORS : usize = 3;
+/// Hive life lost each time the player dies.
+pub const DEATH_PENALTY : f32 = HIVE_MAX_LIFE*0.1;
+/// Deaths it takes to lose the game.
+pub const MAX_DEATHS : usize = 3;
+
+/// The colony on the surface, free of any window or GL state.
+/// Scenes only read from it to draw, and drive it through `step`.
+pub struct Simulation {
+	pub objs : ObjectSet<Gobj>,
+	pub player_id : GameObjectID,
+	pub world : Rc<RefCell<World>>,
+	pub hive : Rc<RefCell<Hive>>,
+	pub spawn_queue : Rc<RefCell<Vec<Gobj>>>,
+	food_timer : f32,
+	predator_timer : f32,
+	pub deaths : usize,
+}
+impl Simulation {
+	/// Same seed and same inputs give the same colony.
+	pub fn new(hive : Rc<RefCell<Hive>>, seed : u64, trails : TrailBackend) -> Self {
+		Simulation {
+			objs: ObjectSet::new(),
+			player_id: 0,
+			world: Rc::new(RefCell::new(World::new(hive.clone(), seed, trails))),
+			hive,
+			spawn_queue: Rc::new(RefCell::new(Vec::new())),
+			food_timer: 0.,
+			predator_timer: PREDATOR_TIMER_HIGH,
+			deaths: 0,
+		}
+	}
+	/// `col_map` can come from `Texture2D::get_texture_data`, or straight
+	/// from `Image::from_file_with_format(TEX_MAP, None)` when running headless.
+	pub fn init(&mut self, col_map : &Image) {
+		self.world.borrow_mut().init(col_map, &MAP_DIMS);
+		self.spawn_player();
+	}
+	/// Back to a fresh colony on the same map. The RNG carries on rather than
+	/// being reseeded, so a replay that restarts still plays back exactly.
+	pub fn reset(&mut self) {
+		self.objs = ObjectSet::new();
+		self.spawn_queue.borrow_mut().clear();
+		self.world.borrow_mut().reset();
+		*self.hive.borrow_mut() = Hive::new();
+		self.food_timer = 0.;
+		self.predator_timer = PREDATOR_TIMER_HIGH;
+		self.deaths = 0;
+		self.spawn_player();
+	}
+	fn spawn_player(&mut self) {
+		self.player_id = self.objs.create(
+			Gobj::new_player(
+				self.spawn_queue.clone(),
+				self.world.clone(),
+				&Vec2::ZERO
+				)
+			);
+	}
+	pub fn presim(&mut self) {
+		for _ in 0..100 {
+			let pos = random_ring_point(
+				&HOME_POS,
+				ANT_RAD,
+				ANT_RAD*3.,
+				&mut self.world.borrow_mut().rng
+			);
+			self.objs.create(Gobj::new_ant(
+					self.spawn_queue.clone(),
+					self.world.clone(),
+					&pos
+				));
+		}
+		for _ in 0..PRESIM_STEPS {
+			self.step(FIXED_DT, &FrameInput::default());
+		}
+	}
+	pub fn step(&mut self, d : f32, input : &FrameInput) {
+		let emerged = self.hive.borrow_mut().update(d);
+		for _ in 0..emerged {
+			let pos = random_ring_point(&HOME_POS, 0., ANT_RAD*3., &mut self.world.borrow_mut().rng);
+			self.objs.create(Gobj::new_ant(self.spawn_queue.clone(), self.world.clone(), &pos));
+		}
+		self.index_ants();
+		self.objs.update(d, input);
+		self.relay_food();
+		self.remove_eaten();
+		self.world.borrow_mut().update_trails(d);
+
+		if self.food_timer >= 0. {
+			self.food_timer -= d;
+		} else if self.hive.borrow().state_as_float() < 0.9 {
+			self.spawn_food();
+			self.food_timer = self.world.borrow_mut().rng.gen_range(FOOD_TIMER_LOW, FOOD_TIMER_HIGH);
+		}
+
+		if self.predator_timer >= 0. {
+			self.predator_timer -= d;
+		} else {
+			self.spawn_predator();
+			let fed = self.hive.borrow().state_as_float().clamp(0., 1.);
+			let rng = &mut self.world.borrow_mut().rng;
+			self.predator_timer = lerp(PREDATOR_TIMER_HIGH, PREDATOR_TIMER_LOW, fed)*rng.gen_range(0.75, 1.25);
+		}
+
+		for obj in self.spawn_queue.borrow().iter() {
+			self.objs.create(obj.clone());
+		}
+		self.spawn_queue.borrow_mut().clear();
+		self.world.borrow_mut().refresh_home_trail(self.spawn_queue.clone());
+	}
+	fn index_ants(&mut self) {
+		let world = &mut *self.world.borrow_mut();
+		world.ants.clear();
+		for (id, o) in self.objs.objects.iter() {
+			if let Gobj::Ant(_, _, pos, ..) = o {
+				world.ants.insert(*pos, *id);
+			}
+		}
+	}
+	/// Loaded ants pass their food to idle ones they bump into, who carry it
+	/// the rest of the way home.
+	fn relay_food(&mut self) {
+		let mut handoffs : Vec<(GameObjectID, GameObjectID, Vec2)> = Vec::new();
+		{
+			let world = self.world.borrow();
+			for (id, o) in self.objs.objects.iter() {
+				let Some((pos, food)) = o.relay_food() else { continue };
+				let taker = world.ants_near(&pos, ANT_RELAY_RANGE)
+					.filter(|(_, t)| !handoffs.iter().any(|(_, h, _)| h == t))
+					.filter(|(_, t)| self.objs.objects.get(t).is_some_and(|o| o.can_take(food)))
+					.filter(|(tp, _)| world.reachable(&pos, tp))
+					.min_by(|a, b| a.0.distance(pos).total_cmp(&b.0.distance(pos)));
+				if let Some((_, t)) = taker {
+					handoffs.push((*id, *t, pos));
+				}
+			}
+		}
+		for (from, to, from_pos) in handoffs {
+			let Some(food) = self.objs.get_obj_mut(from).give_food() else { continue };
+			self.objs.get_obj_mut(to).receive_food(food, &from_pos);
+		}
+	}
+	/// Drops what the player carried in a burst of particles and takes the
+	/// penalty out of the hive. They stay down until `respawn_player`.
+	pub fn kill_player(&mut self) {
+		let Some(pos) = self.objs.get_obj_mut(self.player_id).kill_player() else { return };
+		self.deaths += 1;
+		self.hive.borrow_mut().life -= DEATH_PENALTY;
+		let p = Gobj::new_particles(
+			&pos,
+			40,
+			2.,
+			PLAYER_RAD*2.,
+			COL_MARKER_ALARM,
+			ParticleStyle::Explosive(40., 1.5),
+			&mut self.world.borrow_mut().rng);
+		self.objs.create(p);
+	}
+	/// Whether the player's out of health, either just now or lying there since.
+	pub fn player_dead(&self) -> bool {
+		self.objs.get_obj(self.player_id).player_health().is_some_and(|h| h <= 0.)
+	}
+	/// Puts the player at `p`, revived if they'd died.
+	pub fn respawn_player(&mut self, p : &Vec2) {
+		self.objs.get_obj_mut(self.player_id).revive_player();
+		self.set_player_pos(p);
+	}
+	fn remove_eaten(&mut self) {
+		let eaten = std::mem::take(&mut self.world.borrow_mut().eaten);
+		for id in eaten {
+			let Some(ant) = self.objs.objects.remove(&id) else { continue };
+			ant.drop_food();
+			let Gobj::Ant(_, _, pos, ..) = ant else { continue };
+			let p = Gobj::new_particles(
+				&pos,
+				12,
+				1.2,
+				ANT_RAD,
+				COL_MARKER_ALARM,
+				ParticleStyle::Explosive(25., 2.),
+				&mut self.world.borrow_mut().rng);
+			self.objs.create(p);
+		}
+	}
+	/// Somewhere out of sight of the player and away from the nest.
+	pub fn spawn_predator(&mut self) {
+		let predators = self.objs.objects.values().filter(|o| matches!(o, Gobj::Predator(..))).count();
+		if predators >= MAX_PREDATORS { return }
+		let player_pos = self.player_pos();
+		let (pos, kind) = {
+			let world = &mut *self.world.borrow_mut();
+			let pos = loop {
+				let p = world.random_free_pos(MAP_DIMS.x*0.25, MAP_DIMS.x/2.);
+				if p.distance(player_pos) > W { break p }
+			};
+			(pos, PREDATOR_KINDS[world.rng.gen_range(0, PREDATOR_KINDS.len())])
+		};
+		self.objs.create(Gobj::new_predator(self.spawn_queue.clone(), self.world.clone(), &pos, kind));
+	}
+	pub fn lost(&self) -> bool { self.hive.borrow().lost() }
+
+	pub fn write_state(&self, w : &mut ByteWriter) {
+		self.objs.write_state(w, |w, o| o.write(w));
+		w.usize(self.player_id);
+		self.world.borrow().write_state(w);
+		self.hive.borrow().write(w);
+		w.f32(self.food_timer);
+		w.f32(self.predator_timer);
+		w.usize(self.deaths);
+	}
+	pub fn read_state(&mut self, r : &mut ByteReader) -> io::Result<()> {
+		let (sq, world) = (self.spawn_queue.clone(), self.world.clone());
+		self.objs.read_state(r, |r| Gobj::read(r, sq.clone(), world.clone()))?;
+		self.player_id = r.usize()?;
+		self.world.borrow_mut().read_state(r)?;
+		*self.hive.borrow_mut() = Hive::read(r)?;
+		self.food_timer = r.f32()?;
+		self.predator_timer = r.f32()?;
+		self.deaths = r.usize()?;
+		self.spawn_queue.borrow_mut().clear();
+		Ok(())
+	}
+
+	pub fn spawn_food(&mut self) {
+		let player_pos = self.player_pos();
+		let world = &mut *self.world.borrow_mut();
+		for _ in 0..3 {
+			let mut place;
+			loop {
+				place = random_ring_point(&HOME_POS, MAP_DIMS.x*0.1, MAP_DIMS.x/2., &mut world.rng);
+				if !world.is_collision(&place)
+					&& place.distance(player_pos) > W {
+						break;
+				}
+			}
+			for _ in 0..world.rng.gen_range(1, 4) {
+				let pos = random_ring_point(
+					&place,
+					0.,
+					ANT_RAD*6.,
+					&mut world.rng
+				);
+				let pos = world.free_pos(&pos);
+				let amount = world.rng.gen_range(FOOD_SOURCE_MIN, FOOD_SOURCE_MAX);
+				let f = Food::new(&pos, amount, &mut world.rng);
+				world.food.put_food(f);
+			}
+		}
+	}
+	pub fn player_pos(&self) -> Vec2 {
+		match self.objs.get_obj(self.player_id) {
+			Gobj::Player(_, _, pos, ..) => *pos,
+			_ => panic!("player_id ({}) not pointing to a Player!", self.player_id)
+		}
+	}
+	pub fn player_render_pos(&self, rd : &RenderData) -> Vec2 {
+		self.objs.get_obj(self.player_id).render_pos(rd).unwrap()
+	}
+	pub fn player_facing(&self) -> Vec2 {
+		match self.objs.get_obj(self.player_id) {
+			Gobj::Player(.., facing, _, _) => *facing,
+			_ => panic!("player_id ({}) not pointing to a Player!", self.player_id)
+		}
+	}
+	pub fn set_player_pos(&mut self, p : &Vec2) {
+		let p = self.world.borrow().free_pos(p);
+		match self.objs.get_obj_mut(self.player_id) {
+			Gobj::Player(_, _, pos, prev_pos, ..) => { *pos = p; *prev_pos = p; },
+			_ => panic!("player_id ({}) not pointing to a Player!", self.player_id)
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::ants::{AntState, Caste};
+	use crate::markers::{ALARM_DEPOSIT, MARKER_MERGE_RADIUS};
+	use crate::pheromones::Pheromone;
+
+	/// A colony on the real map, with no window behind it.
+	fn headless(seed : u64) -> Simulation {
+		let mut sim = Simulation::new(Rc::new(RefCell::new(Hive::new())), seed, TrailBackend::Markers);
+		sim.init(&Image::from_file_with_format(TEX_MAP, None));
+		sim
+	}
+	fn count_ants(sim : &Simulation) -> usize {
+		sim.objs.objects.values().filter(|o| matches!(o, Gobj::Ant(..))).count()
+	}
+
+	#[test]
+	fn presim_runs() {
+		let mut sim = headless(1);
+		sim.presim();
+		assert!(count_ants(&sim) > 0);
+		assert!(!sim.lost());
+	}
+	#[test]
+	fn ant_brings_food_home() {
+		let mut sim = headless(2);
+		let food_pos = sim.world.borrow().free_pos(&(HOME_POS + vec2(ANT_HOME_DEPOSIT_RANGE*2., 0.)));
+		let food_id = {
+			let world = &mut *sim.world.borrow_mut();
+			let f = Food::new(&food_pos, FOOD_SOURCE_MIN, &mut world.rng);
+			world.food.put_food(f)
+		};
+		let ant = sim.objs.create(Gobj::new_ant_of(sim.spawn_queue.clone(), sim.world.clone(), &food_pos, Caste::Worker));
+		if let Gobj::Ant(.., state, _, _, _) = sim.objs.get_obj_mut(ant) {
+			*state = AntState::GetFood(food_id);
+		}
+
+		let mut picked_up = false;
+		for _ in 0..60*60 {
+			sim.step(FIXED_DT, &FrameInput::default());
+			let Gobj::Ant(_, _, pos, .., state, _, _, _) = sim.objs.get_obj(ant) else { panic!("ant {ant} is gone") };
+			match state {
+				AntState::GoHome(..) => picked_up = true,
+				_ if picked_up => {
+					assert!(pos.distance(HOME_POS) < ANT_HOME_DEPOSIT_RANGE + ANT_SPEED*FIXED_DT);
+					assert_eq!(sim.world.borrow().trips.trips, 1);
+					return;
+				},
+				_ => (),
+			}
+		}
+		panic!("food never made it home (picked up: {picked_up})");
+	}
+	#[test]
+	fn hungry_ant_gets_home() {
+		let mut sim = headless(1);
+		// with the map covered in wander trails
+		sim.presim();
+		let start = sim.world.borrow().free_pos(&(HOME_POS + vec2(150., 0.)));
+		let ant = sim.objs.create(Gobj::new_ant_of(sim.spawn_queue.clone(), sim.world.clone(), &start, Caste::Worker));
+		if let Gobj::Ant(.., state, _, energy, _) = sim.objs.get_obj_mut(ant) {
+			*state = AntState::Hungry;
+			*energy = ANT_MAX_ENERGY*0.25;
+		}
+
+		for _ in 0..60*60 {
+			sim.step(FIXED_DT, &FrameInput::default());
+			let Some(Gobj::Ant(_, _, pos, .., state, _, energy, _)) = sim.objs.objects.get(&ant) else { panic!("ant {ant} starved") };
+			if !matches!(state, AntState::Hungry) {
+				assert!(pos.distance(HOME_POS) < ANT_HOME_DEPOSIT_RANGE + ANT_SPEED*FIXED_DT);
+				// topped up, less what it's burnt since
+				assert!(*energy > ANT_MAX_ENERGY*0.95, "only got {energy}");
+				return;
+			}
+		}
+		panic!("ant never made it home");
+	}
+	#[test]
+	fn same_seed_same_run() {
+		let (mut a, mut b) = (headless(4), headless(4));
+		a.presim();
+		b.presim();
+		for i in 0..600 {
+			// walk in circles, swapping trails and sounding the alarm now and then
+			let t = i as f32*0.05;
+			let input = FrameInput {
+				iv: vec2(t.cos(), t.sin()),
+				toggle_marker: i % 90 == 0,
+				alarm: i % 200 == 0,
+				..FrameInput::default()
+			};
+			a.step(FIXED_DT, &input);
+			b.step(FIXED_DT, &input);
+		}
+		let (mut wa, mut wb) = (ByteWriter::new(), ByteWriter::new());
+		a.write_state(&mut wa);
+		b.write_state(&mut wb);
+		assert_eq!(wa.bytes, wb.bytes);
+	}
+	#[test]
+	fn alarm_spreads_then_dies_out() {
+		let mut sim = headless(5);
+		let at = sim.world.borrow().free_pos(&(HOME_POS + vec2(ANT_HOME_DEPOSIT_RANGE*2., 0.)));
+		for i in 0..12 {
+			let pos = at + Vec2::from_angle(i as f32*0.52)*ANT_RAD*(4. + (i % 3) as f32*2.);
+			sim.objs.create(Gobj::new_ant_of(sim.spawn_queue.clone(), sim.world.clone(), &pos, Caste::Soldier));
+		}
+		sim.world.borrow_mut().raise_alarm(&at, ALARM_DEPOSIT, sim.spawn_queue.clone());
+		// anything outside the merge radius of the first deposit was passed on
+		let passed_on = |sim : &Simulation| {
+			let world = sim.world.borrow();
+			world.smell(Pheromone::Alarm, &at, ANT_RAD*40.) - world.smell(Pheromone::Alarm, &at, MARKER_MERGE_RADIUS)
+		};
+		let mut spread = false;
+		for _ in 0..60*3 {
+			sim.step(FIXED_DT, &FrameInput::default());
+			spread |= passed_on(&sim) > 0.;
+		}
+		assert!(spread, "no soldier passed the alarm on");
+		for _ in 0..60*40 {
+			sim.step(FIXED_DT, &FrameInput::default());
+		}
+		assert_eq!(sim.world.borrow().smell(Pheromone::Alarm, &at, ANT_RAD*40.), 0.);
+	}
+	#[test]
+	fn reset_restores_start() {
+		let fresh = headless(3);
+		let mut sim = headless(3);
+		sim.presim();
+		sim.reset();
+		// reset leaves the RNG running, everything else should be as it started
+		fresh.world.borrow_mut().rng = sim.world.borrow().rng.clone();
+		let (mut a, mut b) = (ByteWriter::new(), ByteWriter::new());
+		fresh.write_state(&mut a);
+		sim.write_state(&mut b);
+		assert_eq!(a.bytes, b.bytes);
+		assert_eq!(count_ants(&sim), 0);
+	}
+}
@@ -1,60 +1,242 @@
 use std::rc::Rc;
-use std::{collections::HashMap, cell::RefCell};
+use std::{cell::RefCell, io};
 
-use macroquad::{prelude::{Vec2, vec2}, texture::Texture2D};
+use macroquad::{prelude::Vec2, texture::Image};
 
+use crate::collision::CollisionMap;
+use crate::pathfinding::{FlowField, find_path};
+use crate::game_objects::RenderData;
+use crate::ants::Antennae;
+use crate::gobj::{Gobj, HOME_POS, ANT_RAD};
 use crate::hive::Hive;
-use crate::{food::FoodWorld, markers::MarkerWorld};
+use crate::markers::{Marker, MARKER_DEPOSIT, MARKER_MERGE_RADIUS};
+use crate::pheromones::{PheromoneField, Pheromone};
+use crate::game_objects::GameObjectID;
+use crate::prelude::{Rng, ByteWriter, ByteReader, invalid_data, arg_value, SpatialGrid, random_ring_point};
+use crate::{food::{FoodWorld, FOOD_SEARCH_RANGE}, markers::{MarkerWorld, MARKER_SEARCH_RANGE}};
 
-pub const COLLISION_GRID_RESOLUTION : f32 = 4.;
 const MAP_SIZE : f32 = 1500.;
 pub const MAP_DIMS : Vec2 = Vec2::new(MAP_SIZE, MAP_SIZE);
 pub const MAP_TOPLEFT : Vec2 = Vec2::new(-MAP_DIMS.x/2., -MAP_DIMS.y/2.);
+/// Which model ants lay and follow trails in, picked with `--trails markers|field`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TrailBackend {
+	Markers,
+	Field,
+}
+impl TrailBackend {
+	pub fn from_args() -> Self {
+		match arg_value("--trails").as_deref() {
+			Some("field") => TrailBackend::Field,
+			_ => TrailBackend::Markers,
+		}
+	}
+	pub fn write(&self, w : &mut ByteWriter) {
+		w.u8(*self as u8);
+	}
+	pub fn read(r : &mut ByteReader) -> io::Result<Self> {
+		match r.u8()? {
+			0 => Ok(TrailBackend::Markers),
+			1 => Ok(TrailBackend::Field),
+			t => Err(invalid_data(&format!("bad TrailBackend tag {t}"))),
+		}
+	}
+}
+/// How far food gets carried in one go, from pickup or handoff to
+/// handoff or home.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct TripStats {
+	pub trips : usize,
+	pub distance : f32,
+}
+impl TripStats {
+	pub fn record(&mut self, distance : f32) {
+		self.trips += 1;
+		self.distance += distance;
+	}
+	pub fn average(&self) -> Option<f32> {
+		if self.trips == 0 { return None }
+		Some(self.distance/self.trips as f32)
+	}
+}
+/// Least amount of pheromone an antenna notices.
+const SMELL_THRESHOLD : f32 = 0.05;
 #[derive(Debug)]
 pub struct World {
 	pub food : FoodWorld,
 	pub marker : MarkerWorld,
+	pub field : PheromoneField,
+	pub trails : TrailBackend,
 
-	pub collision_map : HashMap<(i32, i32), bool>,
-	pub collision_cell_size : Vec2,
+	pub collision : CollisionMap,
+	/// Walking distance to `HOME_POS`, for ants that lost the trail.
+	pub home_flow : FlowField,
 	pub map_tl : Vec2,
 
+	/// Every ant by position, rebuilt once a step.
+	pub ants : SpatialGrid<GameObjectID>,
+	pub trips : TripStats,
+	/// Where the player was last step, for anything hunting them. None while they're down.
+	pub player_pos : Option<Vec2>,
+	/// Ants caught this step, for the simulation to remove.
+	pub eaten : Vec<GameObjectID>,
+	/// Damage dealt to the player since they last updated.
+	pub player_damage : f32,
+
 	pub hive : Rc<RefCell<Hive>>,
+	pub rng : Rng,
 }
-pub const FOOD_WORLD_GRID_SIZE : f32 = 42.;
-pub const MARK_WORLD_GRID_SIZE : f32 = 55.;
+// one cell per search radius, so a query never touches more than 3x3 cells
+pub const FOOD_WORLD_GRID_SIZE : f32 = FOOD_SEARCH_RANGE;
+pub const MARK_WORLD_GRID_SIZE : f32 = MARKER_SEARCH_RANGE;
+const ANT_GRID_SIZE : f32 = ANT_RAD*8.;
 impl World {
-	pub fn new(hive : Rc<RefCell<Hive>>) -> Self {
+	pub fn new(hive : Rc<RefCell<Hive>>, seed : u64, trails : TrailBackend) -> Self {
 		World {
 			food: FoodWorld::new(FOOD_WORLD_GRID_SIZE),
 			marker: MarkerWorld::new(MARK_WORLD_GRID_SIZE),
-			collision_map: HashMap::new(),
-			collision_cell_size: Vec2::ZERO,
+			field: PheromoneField::new(MAP_TOPLEFT, &Vec2::ZERO),
+			trails,
+			collision: CollisionMap::new(MAP_TOPLEFT),
+			home_flow: FlowField::empty(),
 			map_tl: MAP_TOPLEFT,
-			hive
+			ants: SpatialGrid::new(ANT_GRID_SIZE),
+			trips: TripStats::default(),
+			player_pos: None,
+			eaten: Vec::new(),
+			player_damage: 0.,
+			hive,
+			rng: Rng::new(seed),
 		}
 	}
-	pub fn init(&mut self, col_map : &Texture2D, map_dims : &Vec2) {
-		let td = col_map.get_texture_data();
-
-		let w = col_map.width()/COLLISION_GRID_RESOLUTION;
-		let h = col_map.height()/COLLISION_GRID_RESOLUTION;
-		self.collision_cell_size = *map_dims / vec2(w, h);
-
-		for x in 0..(w as i32) {
-			for y in 0..(h as i32) {
-				self.collision_map.insert((x, y),
-				td.get_pixel(
-					((x as f32 + 0.5) * COLLISION_GRID_RESOLUTION) as u32,
-					((y as f32 + 0.5) * COLLISION_GRID_RESOLUTION) as u32
-				).a > 0.29);
-			}
+	pub fn init(&mut self, col_map : &Image, map_dims : &Vec2) {
+		self.collision = CollisionMap::from_image(col_map, self.map_tl, map_dims);
+		self.home_flow = FlowField::new(&self.collision, &HOME_POS);
+		self.field = PheromoneField::new(self.map_tl, map_dims);
+	}
+	/// Drops all food and markers, keeping the collision map from `init`.
+	pub fn reset(&mut self) {
+		self.food = FoodWorld::new(FOOD_WORLD_GRID_SIZE);
+		self.marker = MarkerWorld::new(MARK_WORLD_GRID_SIZE);
+		self.field.clear();
+		self.ants.clear();
+		self.trips = TripStats::default();
+		self.eaten.clear();
+		self.player_damage = 0.;
+	}
+	/// Only what changes while playing; the collision map is rebuilt by `init`.
+	pub fn write_state(&self, w : &mut ByteWriter) {
+		self.food.write_state(w);
+		self.marker.write_state(w);
+		self.trails.write(w);
+		if self.trails == TrailBackend::Field {
+			self.field.write_state(w);
+		}
+		w.usize(self.trips.trips);
+		w.f32(self.trips.distance);
+		w.u64(self.rng.state());
+	}
+	pub fn read_state(&mut self, r : &mut ByteReader) -> io::Result<()> {
+		self.food.read_state(r)?;
+		self.marker.read_state(r)?;
+		self.trails = TrailBackend::read(r)?;
+		if self.trails == TrailBackend::Field {
+			self.field.read_state(r)?;
 		}
+		self.trips = TripStats { trips: r.usize()?, distance: r.f32()? };
+		self.rng = Rng::from_state(r.u64()?);
+		Ok(())
+	}
+	pub fn create_marker(&mut self, m : Marker, spawn_queue : Rc<RefCell<Vec<Gobj>>>) {
+		match self.trails {
+			TrailBackend::Markers => self.marker.create_marker(m, spawn_queue, &mut self.rng),
+			TrailBackend::Field => self.field.deposit(m.pheromone(), m.pos(), *m.strength()),
+		}
+	}
+	/// Warns every ant that can smell `pos` that something's wrong there.
+	pub fn raise_alarm(&mut self, pos : &Vec2, strength : f32, spawn_queue : Rc<RefCell<Vec<Gobj>>>) {
+		self.create_marker(Marker::Alarm(*pos, strength), spawn_queue);
+	}
+	/// Keeps the nest itself smelling of home, so there's always a trail to end on.
+	pub fn refresh_home_trail(&mut self, spawn_queue : Rc<RefCell<Vec<Gobj>>>) {
+		match self.trails {
+			TrailBackend::Markers =>
+				if !self.marker
+					.local_markers(&HOME_POS, &Vec2::ZERO, &|_| true)
+						.iter()
+						.any(|m| m.pos().distance(HOME_POS) <= MARKER_MERGE_RADIUS) {
+					self.create_marker(Marker::Home(HOME_POS, MARKER_DEPOSIT), spawn_queue);
+				},
+			TrailBackend::Field =>
+				if self.field.sample(Pheromone::Home, &HOME_POS) < MARKER_DEPOSIT {
+					self.field.deposit(Pheromone::Home, &HOME_POS, MARKER_DEPOSIT);
+				},
+		}
+	}
+	pub fn update_trails(&mut self, d : f32) {
+		match self.trails {
+			TrailBackend::Markers => self.marker.update(d),
+			TrailBackend::Field => self.field.update(d),
+		}
+	}
+	/// How much of `p` there is around `at`.
+	pub fn smell(&self, p : Pheromone, at : &Vec2, radius : f32) -> f32 {
+		match self.trails {
+			TrailBackend::Markers => self.marker.strength_near(p, at, radius),
+			TrailBackend::Field => self.field.sample(p, at),
+		}
+	}
+	/// Which way the ant's antennae say the trail goes, if they pick one up.
+	/// Probes behind a wall smell nothing.
+	pub fn sense(&self, p : Pheromone, pos : &Vec2, heading : &Vec2, antennae : &Antennae) -> Option<Vec2> {
+		let heading = if *heading == Vec2::ZERO { Vec2::X } else { *heading };
+		// centre last, since `max_by` keeps the last of equal probes and going
+		// straight on an even trail beats weaving
+		[-antennae.angle, antennae.angle, 0.]
+			.iter()
+			.map(|a| Vec2::from_angle(*a).rotate(heading))
+			.map(|dir| {
+				let probe = *pos + dir*antennae.distance;
+				let s = if self.reachable(pos, &probe) { self.smell(p, &probe, antennae.radius) } else { 0. };
+				(dir, s)
+			})
+			.filter(|(_, s)| *s >= SMELL_THRESHOLD)
+			.max_by(|a, b| a.1.total_cmp(&b.1))
+			.map(|(dir, _)| dir)
+	}
+	pub fn render_trails(&self, rd : &RenderData) {
+		match self.trails {
+			TrailBackend::Markers => self.marker.render(rd),
+			TrailBackend::Field => self.field.render(rd),
+		}
+	}
+	/// Ants within `radius` of `pos`, as of the start of this step.
+	pub fn ants_near(&self, pos : &Vec2, radius : f32) -> impl Iterator<Item = &(Vec2, GameObjectID)> {
+		self.ants.query_radius(pos, radius)
 	}
 	pub fn is_collision(&self, pos : &Vec2) -> bool {
-		let key = (*pos - self.map_tl)/self.collision_cell_size;
-		let key = (key.x.floor() as i32, key.y.floor() as i32);
-		if !self.collision_map.contains_key(&key) { true }
-		else { *self.collision_map.get(&key).unwrap() }
+		self.collision.is_collision(pos)
+	}
+	/// Waypoints from `from` to `to` around walls, if there's a way through.
+	pub fn find_path(&self, from : &Vec2, to : &Vec2) -> Option<Vec<Vec2>> {
+		find_path(&self.collision, from, to)
+	}
+	pub fn home_direction(&self, pos : &Vec2) -> Option<Vec2> {
+		self.home_flow.direction(&self.collision, pos)
+	}
+	/// A free spot between `min` and `max` away from home.
+	pub fn random_free_pos(&mut self, min : f32, max : f32) -> Vec2 {
+		let p = random_ring_point(&HOME_POS, min, max, &mut self.rng);
+		self.free_pos(&p)
+	}
+	/// `pos` if it's free, otherwise the centre of the closest free cell.
+	/// Falls back on `pos` when there's no free space at all.
+	pub fn free_pos(&self, pos : &Vec2) -> Vec2 {
+		if !self.is_collision(pos) { return *pos }
+		self.collision.nearest_free_cell(pos).unwrap_or(*pos)
+	}
+	/// Whether an agent at `from` could walk straight to `to`.
+	pub fn reachable(&self, from : &Vec2, to : &Vec2) -> bool {
+		self.collision.segment_clear(from, to)
 	}
 }